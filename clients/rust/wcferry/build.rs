@@ -3,7 +3,7 @@
         .build_client(true)
         .build_server(false)
         .out_dir("src/proto")
-        .compile(&["proto/wcf.proto"], &["."])
+        .compile(&["proto/wcf.proto", "proto/roomdata.proto"], &["."])
         .expect("failed to compile protos");
     Ok(())
 }
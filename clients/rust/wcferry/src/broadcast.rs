@@ -0,0 +1,453 @@
+use std::{
+    collections::HashMap,
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+use log::{info, warn};
+
+use crate::{
+    error::WcfError,
+    limiter::{MsgKind, Rate, RateLimiter},
+    receiver::Receiver,
+    status::SendStatus,
+    template::Template,
+    wechat::{self, wcf, WeChat},
+    xmlmsg::XmlMessage,
+};
+
+/** 未配置任何限流器时群发使用的默认速率 */
+const DEFAULT_RATE_PER_MINUTE: u32 = 20;
+const DEFAULT_JITTER: Duration = Duration::from_secs(2);
+
+/** 群发内容 */
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Content {
+    /** 原样发送的文本，需要按接收人替换变量时使用 Template */
+    Text(String),
+    /** 按接收人渲染的模板，见 template 模块 */
+    Template(Template),
+    Image(PathBuf),
+    File(PathBuf),
+    Xml(XmlMessage),
+    Emotion(PathBuf),
+}
+
+impl Content {
+    fn kind(&self) -> MsgKind {
+        match self {
+            Content::Text(_) | Content::Template(_) => MsgKind::Text,
+            Content::Image(_) => MsgKind::Image,
+            Content::File(_) => MsgKind::File,
+            Content::Xml(_) => MsgKind::Xml,
+            Content::Emotion(_) => MsgKind::Emotion,
+        }
+    }
+
+    fn needs_contact(&self) -> bool {
+        match self {
+            Content::Template(template) => template.needs_contact(),
+            _ => false,
+        }
+    }
+}
+
+/** 发送进度，每处理完一个接收人回调一次 */
+#[derive(Debug)]
+pub struct Progress<'a> {
+    /** 已处理数量（含本次） */
+    pub done: usize,
+    pub total: usize,
+    pub receiver: &'a Receiver,
+    /** 本次失败原因，成功时为 None */
+    pub error: Option<&'a str>,
+}
+
+/** 发送失败的接收人 */
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Failure {
+    pub receiver: Receiver,
+    pub reason: String,
+}
+
+/** 群发结果 */
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct BroadcastReport {
+    pub sent: Vec<Receiver>,
+    pub failed: Vec<Failure>,
+    /** 被取消时为 true，可再次调用 run 从中断处继续 */
+    pub cancelled: bool,
+    /** FailFast 限流器拒绝时为 true，当前接收人未发送，稍后再次调用 run 从它继续 */
+    pub rate_limited: bool,
+}
+
+/**
+ * 群发任务，可取消、可从中断处继续
+ * @example let mut task = Broadcast::new(targets, Content::Template(Template::compile("{name}，你好")?)); task.run(&mut wechat, |p| println!("{}/{}", p.done, p.total))?;
+ */
+#[derive(Debug)]
+pub struct Broadcast {
+    targets: Vec<Receiver>,
+    content: Content,
+    limiter: Option<Arc<RateLimiter>>,
+    /** 未配置任何限流器时使用，只创建一次，取消后继续发送不会重新获得突发额度 */
+    default_limiter: Arc<RateLimiter>,
+    cancel: Arc<AtomicBool>,
+    next: usize,
+    report: BroadcastReport,
+}
+
+impl Broadcast {
+    pub fn new(targets: Vec<Receiver>, content: Content) -> Self {
+        Broadcast {
+            targets,
+            content,
+            limiter: None,
+            default_limiter: Arc::new(
+                RateLimiter::new()
+                    .global(Rate::per_minute(DEFAULT_RATE_PER_MINUTE))
+                    .jitter(DEFAULT_JITTER),
+            ),
+            cancel: Arc::new(AtomicBool::new(false)),
+            next: 0,
+            report: BroadcastReport::default(),
+        }
+    }
+
+    /**
+     * 群发专用限流器，在 WeChat 自身的 rate_limiter 之外额外生效
+     * 两者都未配置时使用每分钟 20 条、随机延迟 0 ~ 2 秒的默认限流
+     */
+    pub fn limiter(mut self, limiter: Arc<RateLimiter>) -> Self {
+        self.limiter = Some(limiter);
+        self
+    }
+
+    /** 取消标记，可在其他线程中置为 true 以中断发送 */
+    pub fn cancel_handle(&self) -> Arc<AtomicBool> {
+        self.cancel.clone()
+    }
+
+    /** 已处理的接收人数量 */
+    pub fn position(&self) -> usize {
+        self.next
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.next >= self.targets.len()
+    }
+
+    pub fn report(&self) -> &BroadcastReport {
+        &self.report
+    }
+
+    /**
+     * 依次发送给剩余的接收人
+     * 被取消后再次调用会清除取消标记并从中断处继续；首次调用前已置位的取消标记不会被清除
+     * @param on_progress: 进度回调
+     */
+    pub fn run<F>(
+        &mut self,
+        wechat: &mut WeChat,
+        on_progress: F,
+    ) -> Result<&BroadcastReport, Box<dyn std::error::Error>>
+    where
+        F: FnMut(&Progress),
+    {
+        let limiter = self.pick_limiter(wechat.rate_limiter.is_some());
+        let contacts = if self.content.needs_contact() {
+            contact_map(wechat)?
+        } else {
+            HashMap::new()
+        };
+        Ok(self.run_with(
+            limiter,
+            |content, receiver| send(wechat, content, receiver, &contacts),
+            on_progress,
+        ))
+    }
+
+    /** 群发专用限流器优先；都未配置时使用默认限流，WeChat 已配置时由 send_* 限流 */
+    fn pick_limiter(&self, wechat_limited: bool) -> Option<Arc<RateLimiter>> {
+        match (&self.limiter, wechat_limited) {
+            (Some(limiter), _) => Some(limiter.clone()),
+            (None, true) => None,
+            (None, false) => Some(self.default_limiter.clone()),
+        }
+    }
+
+    fn run_with<S, F>(
+        &mut self,
+        limiter: Option<Arc<RateLimiter>>,
+        mut send: S,
+        mut on_progress: F,
+    ) -> &BroadcastReport
+    where
+        S: FnMut(&Content, &Receiver) -> Result<SendStatus, WcfError>,
+        F: FnMut(&Progress),
+    {
+        if self.report.cancelled {
+            self.cancel.store(false, Ordering::Relaxed);
+            self.report.cancelled = false;
+        }
+        self.report.rate_limited = false;
+
+        let total = self.targets.len();
+        while self.next < total {
+            if self.cancel.load(Ordering::Relaxed) {
+                info!("群发已取消: {}/{}", self.next, total);
+                self.report.cancelled = true;
+                break;
+            }
+            let receiver = &self.targets[self.next];
+            let result = match &limiter {
+                Some(limiter) => limiter
+                    .acquire(receiver.as_str(), self.content.kind())
+                    .map_err(|_| WcfError::RateLimited),
+                None => Ok(()),
+            }
+            .and_then(|_| send(&self.content, receiver));
+            let error = match result {
+                Ok(SendStatus::Success) => None,
+                Ok(status) => Some(status.to_string()),
+                Err(WcfError::RateLimited) => {
+                    // 未发出，留在队列中等下次 run
+                    info!("群发被限流暂停: {}/{}", self.next, total);
+                    self.report.rate_limited = true;
+                    break;
+                }
+                Err(e) => Some(e.to_string()),
+            };
+            self.next += 1;
+            on_progress(&Progress {
+                done: self.next,
+                total,
+                receiver,
+                error: error.as_deref(),
+            });
+            match error {
+                None => self.report.sent.push(receiver.clone()),
+                Some(reason) => {
+                    warn!("群发失败: {}, {}", receiver, reason);
+                    self.report.failed.push(Failure {
+                        receiver: receiver.clone(),
+                        reason,
+                    });
+                }
+            }
+        }
+        &self.report
+    }
+}
+
+fn contact_map(
+    wechat: &mut WeChat,
+) -> Result<HashMap<String, wcf::RpcContact>, Box<dyn std::error::Error>> {
+    let contacts = wechat::get_contacts(wechat)?.unwrap_or_default();
+    Ok(contacts
+        .contacts
+        .into_iter()
+        .map(|contact| (contact.wxid.clone(), contact))
+        .collect())
+}
+
+fn send(
+    wechat: &mut WeChat,
+    content: &Content,
+    receiver: &Receiver,
+    contacts: &HashMap<String, wcf::RpcContact>,
+) -> Result<SendStatus, WcfError> {
+    match content {
+        Content::Text(msg) => wechat::send_text(wechat, msg.clone(), receiver, &[]),
+        Content::Template(template) => {
+            let msg = template.render(receiver.as_str(), contacts.get(receiver.as_str()));
+            wechat::send_text(wechat, msg, receiver, &[])
+        }
+        Content::Image(path) => wechat::send_image(wechat, path.clone(), receiver),
+        Content::File(path) => wechat::send_file(wechat, path.clone(), receiver),
+        Content::Xml(xml) => xml.send(wechat, receiver),
+        Content::Emotion(path) => wechat::send_emotion(wechat, path.clone(), receiver),
+    }
+}
+
+/**
+ * 群发，一次性发送给所有接收人
+ * @param targets:     接收人列表
+ * @param content:     发送内容
+ * @param on_progress: 进度回调
+ */
+pub fn broadcast<F>(
+    wechat: &mut WeChat,
+    targets: Vec<Receiver>,
+    content: Content,
+    on_progress: F,
+) -> Result<BroadcastReport, Box<dyn std::error::Error>>
+where
+    F: FnMut(&Progress),
+{
+    let mut task = Broadcast::new(targets, content);
+    task.run(wechat, on_progress)?;
+    Ok(task.report)
+}
+
+mod test {
+
+    #[test]
+    fn test_broadcast() {
+        use crate::receiver::{Receiver, SpecialAccount};
+
+        let mut wechat = crate::wechat::WeChat::default();
+        let targets: Vec<Receiver> = vec![SpecialAccount::FileHelper.into()];
+        let template = crate::template::Template::compile("{name}，你好").unwrap();
+        let content = crate::broadcast::Content::Template(template);
+        let report = crate::broadcast::broadcast(&mut wechat, targets, content, |progress| {
+            println!(
+                "{}/{}: {}",
+                progress.done, progress.total, progress.receiver
+            )
+        })
+        .unwrap();
+        println!("Report: {:?}", report);
+    }
+
+    #[test]
+    fn test_cancel_and_resume() {
+        use std::sync::atomic::Ordering;
+
+        use crate::broadcast::{Broadcast, Content};
+        use crate::receiver::Receiver;
+        use crate::status::SendStatus;
+
+        let targets: Vec<Receiver> = ["wxid_a", "wxid_b", "wxid_c"]
+            .iter()
+            .map(|s| s.parse().unwrap())
+            .collect();
+        let mut task = Broadcast::new(targets, Content::Text(String::from("hello")));
+        let cancel = task.cancel_handle();
+
+        // run 之前的取消标记保留
+        cancel.store(true, Ordering::Relaxed);
+        let report = task.run_with(None, |_, _| Ok(SendStatus::Success), |_| {});
+        assert!(report.cancelled);
+        assert!(report.sent.is_empty());
+
+        // 继续后发送一条再取消
+        let report = task.run_with(
+            None,
+            |_, _| {
+                cancel.store(true, Ordering::Relaxed);
+                Ok(SendStatus::Success)
+            },
+            |_| {},
+        );
+        assert!(report.cancelled);
+        assert_eq!(report.sent.len(), 1);
+        assert_eq!(task.position(), 1);
+
+        let mut sent = vec![];
+        let report = task.run_with(
+            None,
+            |_, receiver| {
+                sent.push(receiver.to_string());
+                Ok(SendStatus::Success)
+            },
+            |_| {},
+        );
+        assert!(!report.cancelled);
+        assert_eq!(report.sent.len(), 3);
+        assert_eq!(sent, vec!["wxid_b", "wxid_c"]);
+        assert!(task.is_finished());
+    }
+
+    #[test]
+    fn test_failure_report() {
+        use crate::broadcast::{Broadcast, Content, Failure};
+        use crate::error::WcfError;
+        use crate::receiver::Receiver;
+        use crate::status::SendStatus;
+
+        let targets: Vec<Receiver> = ["wxid_a", "wxid_b", "wxid_c"]
+            .iter()
+            .map(|s| s.parse().unwrap())
+            .collect();
+        let mut task = Broadcast::new(targets.clone(), Content::Text(String::from("hello")));
+        let mut errors = vec![];
+        let report = task.run_with(
+            None,
+            |_, receiver| match receiver.as_str() {
+                "wxid_a" => Ok(SendStatus::Success),
+                "wxid_b" => Ok(SendStatus::Unknown(-1)),
+                _ => Err(WcfError::Timeout(String::from("发送确认超时"))),
+            },
+            |progress| errors.push(progress.error.map(String::from)),
+        );
+        assert_eq!(report.sent, vec![targets[0].clone()]);
+        assert_eq!(
+            report.failed,
+            vec![
+                Failure {
+                    receiver: targets[1].clone(),
+                    reason: SendStatus::Unknown(-1).to_string(),
+                },
+                Failure {
+                    receiver: targets[2].clone(),
+                    reason: WcfError::Timeout(String::from("发送确认超时")).to_string(),
+                },
+            ]
+        );
+        assert_eq!(errors.len(), 3);
+        assert!(errors[0].is_none());
+    }
+
+    #[test]
+    fn test_rate_limited_requeue() {
+        use std::{sync::Arc, time::Duration};
+
+        use crate::broadcast::{Broadcast, Content};
+        use crate::limiter::{LimitMode, Rate, RateLimiter};
+        use crate::receiver::Receiver;
+        use crate::status::SendStatus;
+
+        let targets: Vec<Receiver> = ["wxid_a", "wxid_b"]
+            .iter()
+            .map(|s| s.parse().unwrap())
+            .collect();
+        let limiter = Arc::new(
+            RateLimiter::new()
+                .global(Rate::new(1, Duration::from_secs(3600)))
+                .mode(LimitMode::FailFast),
+        );
+        let mut task = Broadcast::new(targets, Content::Text(String::from("hello")));
+        let report = task.run_with(Some(limiter), |_, _| Ok(SendStatus::Success), |_| {});
+        assert!(report.rate_limited);
+        assert_eq!(report.sent.len(), 1);
+        assert!(report.failed.is_empty());
+        assert_eq!(task.position(), 1);
+
+        let report = task.run_with(None, |_, _| Ok(SendStatus::Success), |_| {});
+        assert!(!report.rate_limited);
+        assert_eq!(report.sent.len(), 2);
+    }
+
+    #[test]
+    fn test_pick_limiter() {
+        use std::sync::Arc;
+
+        use crate::broadcast::{Broadcast, Content};
+        use crate::limiter::RateLimiter;
+
+        let task = Broadcast::new(vec![], Content::Text(String::from("hello")));
+        let default = task.pick_limiter(false).unwrap();
+        assert!(Arc::ptr_eq(&default, &task.pick_limiter(false).unwrap()));
+        assert!(task.pick_limiter(true).is_none());
+
+        let custom = Arc::new(RateLimiter::new());
+        let task = task.limiter(custom.clone());
+        assert!(Arc::ptr_eq(&task.pick_limiter(true).unwrap(), &custom));
+        assert!(Arc::ptr_eq(&task.pick_limiter(false).unwrap(), &custom));
+    }
+}
@@ -0,0 +1,221 @@
+use std::fmt;
+
+use log::error;
+
+use crate::{
+    receiver::{Receiver, SpecialAccount, CHATROOM_SUFFIX, OFFICIAL_PREFIX},
+    wechat::{self, wcf, WeChat},
+};
+
+/** 联系人类别 */
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum ContactKind {
+    Friend,
+    Chatroom,
+    /** 公众号（gh_ 开头） */
+    Official,
+    /** 文件传输助手等系统账号 */
+    Special(SpecialAccount),
+}
+
+impl ContactKind {
+    pub fn from_wxid(wxid: &str) -> ContactKind {
+        if let Some(account) = SpecialAccount::from_name(wxid) {
+            ContactKind::Special(account)
+        } else if wxid.ends_with(CHATROOM_SUFFIX) {
+            ContactKind::Chatroom
+        } else if wxid.starts_with(OFFICIAL_PREFIX) {
+            ContactKind::Official
+        } else {
+            ContactKind::Friend
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum Gender {
+    #[default]
+    Unknown,
+    Male,
+    Female,
+}
+
+impl Gender {
+    /** RpcContact.gender：1 男，2 女，其他未知 */
+    pub fn from_code(code: i32) -> Gender {
+        match code {
+            1 => Gender::Male,
+            2 => Gender::Female,
+            _ => Gender::Unknown,
+        }
+    }
+}
+
+impl fmt::Display for Gender {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Gender::Unknown => write!(f, ""),
+            Gender::Male => write!(f, "男"),
+            Gender::Female => write!(f, "女"),
+        }
+    }
+}
+
+/** 联系人，由 RpcContact 转换并分类 */
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Contact {
+    pub wxid: String,
+    /** 微信号 */
+    pub code: String,
+    pub remark: String,
+    /** 微信昵称 */
+    pub name: String,
+    pub country: String,
+    pub province: String,
+    pub city: String,
+    pub gender: Gender,
+    pub kind: ContactKind,
+}
+
+impl Contact {
+    /** 显示名：优先备注，其次昵称，都为空时为 wxid */
+    pub fn display_name(&self) -> &str {
+        [&self.remark, &self.name, &self.wxid]
+            .into_iter()
+            .find(|s| !s.is_empty())
+            .map(String::as_str)
+            .unwrap_or_default()
+    }
+
+    pub fn is_friend(&self) -> bool {
+        self.kind == ContactKind::Friend
+    }
+
+    /** 作为消息接收人，wxid 不合法时返回错误 */
+    pub fn receiver(&self) -> Result<Receiver, Box<dyn std::error::Error>> {
+        self.wxid.parse()
+    }
+}
+
+impl From<wcf::RpcContact> for Contact {
+    fn from(contact: wcf::RpcContact) -> Self {
+        Contact {
+            kind: ContactKind::from_wxid(&contact.wxid),
+            gender: Gender::from_code(contact.gender),
+            wxid: contact.wxid,
+            code: contact.code,
+            remark: contact.remark,
+            name: contact.name,
+            country: contact.country,
+            province: contact.province,
+            city: contact.city,
+        }
+    }
+}
+
+/**
+ * 获取全部联系人（含群、公众号、系统账号）
+ * 服务端没有返回联系人时报错，避免被当作空列表清空缓存
+ */
+pub fn get_contact_list(wechat: &mut WeChat) -> Result<Vec<Contact>, Box<dyn std::error::Error>> {
+    let contacts = match wechat::get_contacts(wechat)? {
+        Some(contacts) => contacts,
+        None => {
+            error!("获取联系人失败: 服务端未返回联系人");
+            return Err("获取联系人失败".into());
+        }
+    };
+    Ok(contacts.contacts.into_iter().map(Contact::from).collect())
+}
+
+fn get_kind(
+    wechat: &mut WeChat,
+    kind: ContactKind,
+) -> Result<Vec<Contact>, Box<dyn std::error::Error>> {
+    let mut contacts = get_contact_list(wechat)?;
+    contacts.retain(|contact| contact.kind == kind);
+    Ok(contacts)
+}
+
+/** 获取好友列表，不含群、公众号和系统账号 */
+pub fn get_friends(wechat: &mut WeChat) -> Result<Vec<Contact>, Box<dyn std::error::Error>> {
+    get_kind(wechat, ContactKind::Friend)
+}
+
+/** 获取通讯录中的群 */
+pub fn get_chatrooms(wechat: &mut WeChat) -> Result<Vec<Contact>, Box<dyn std::error::Error>> {
+    get_kind(wechat, ContactKind::Chatroom)
+}
+
+/** 获取关注的公众号 */
+pub fn get_official_accounts(
+    wechat: &mut WeChat,
+) -> Result<Vec<Contact>, Box<dyn std::error::Error>> {
+    get_kind(wechat, ContactKind::Official)
+}
+
+mod test {
+
+    #[test]
+    fn test_contact() {
+        use crate::contact::{Contact, ContactKind, Gender};
+        use crate::receiver::SpecialAccount;
+        use crate::wechat::wcf::RpcContact;
+
+        let contact = Contact::from(RpcContact {
+            wxid: String::from("wxid_abc"),
+            name: String::from("张三"),
+            gender: 1,
+            ..Default::default()
+        });
+        assert_eq!(contact.kind, ContactKind::Friend);
+        assert_eq!(contact.gender, Gender::Male);
+        assert_eq!(contact.display_name(), "张三");
+        assert!(contact.receiver().is_ok());
+
+        let contact = Contact::from(RpcContact {
+            wxid: String::from("1688850000000000@openim"),
+            name: String::from("企业微信用户"),
+            ..Default::default()
+        });
+        assert_eq!(contact.kind, ContactKind::Friend);
+        assert!(contact.receiver().is_ok());
+
+        let contact = Contact::from(RpcContact {
+            wxid: String::from("wxid_abc"),
+            remark: String::from("老张"),
+            name: String::from("张三"),
+            gender: 0,
+            ..Default::default()
+        });
+        assert_eq!(contact.gender, Gender::Unknown);
+        assert_eq!(contact.display_name(), "老张");
+
+        let contact = Contact::from(RpcContact {
+            wxid: String::from("wxid_abc"),
+            ..Default::default()
+        });
+        assert_eq!(contact.display_name(), "wxid_abc");
+
+        assert_eq!(
+            ContactKind::from_wxid("34476879773@chatroom"),
+            ContactKind::Chatroom
+        );
+        assert_eq!(ContactKind::from_wxid("gh_abc"), ContactKind::Official);
+        assert_eq!(
+            ContactKind::from_wxid("filehelper"),
+            ContactKind::Special(SpecialAccount::FileHelper)
+        );
+        assert_eq!(
+            ContactKind::from_wxid("newsapp"),
+            ContactKind::Special(SpecialAccount::NewsApp)
+        );
+    }
+
+    #[test]
+    fn test_get_friends() {
+        let mut wechat = crate::wechat::WeChat::default();
+        let friends = crate::contact::get_friends(&mut wechat).unwrap();
+        println!("Friends: {:?}", friends);
+    }
+}
@@ -0,0 +1,318 @@
+use std::{
+    collections::HashMap,
+    time::{Duration, Instant},
+};
+
+use fuzzy_matcher::{skim::SkimMatcherV2, FuzzyMatcher};
+use log::info;
+use pinyin::ToPinyin;
+
+use crate::{
+    contact::{self, Contact},
+    wechat::WeChat,
+};
+
+/** 完全匹配时的得分，高于任何模糊匹配 */
+const EXACT_SCORE: i64 = 1_000_000;
+/** 前缀匹配的额外得分 */
+const PREFIX_BONUS: i64 = 10_000;
+
+/** 参与搜索的字段，按转小写后保存 */
+#[derive(Clone, Debug, Default)]
+struct SearchKeys {
+    keys: Vec<String>,
+}
+
+/** 全拼和首字母，非汉字按原样（小写）保留 */
+fn pinyin_keys(s: &str) -> Option<(String, String)> {
+    if s.is_ascii() {
+        return None;
+    }
+    let mut full = String::new();
+    let mut initials = String::new();
+    for (c, py) in s.chars().zip(s.to_pinyin()) {
+        match py {
+            Some(py) => {
+                full.push_str(py.plain());
+                initials.push_str(py.first_letter());
+            }
+            None if c.is_alphanumeric() => {
+                full.extend(c.to_lowercase());
+                initials.extend(c.to_lowercase());
+            }
+            None => {}
+        }
+    }
+    if full.is_empty() {
+        None
+    } else {
+        Some((full, initials))
+    }
+}
+
+impl SearchKeys {
+    fn new(contact: &Contact) -> Self {
+        let mut keys = vec![];
+        for field in [&contact.remark, &contact.name, &contact.code, &contact.wxid] {
+            if field.is_empty() {
+                continue;
+            }
+            keys.push(field.to_lowercase());
+        }
+        for field in [&contact.remark, &contact.name] {
+            if let Some((full, initials)) = pinyin_keys(field) {
+                keys.push(full);
+                keys.push(initials);
+            }
+        }
+        keys.sort();
+        keys.dedup();
+        SearchKeys { keys }
+    }
+
+    fn score(&self, matcher: &SkimMatcherV2, query: &str) -> Option<i64> {
+        self.keys
+            .iter()
+            .filter_map(|key| {
+                if key == query {
+                    Some(EXACT_SCORE)
+                } else if key.starts_with(query) {
+                    Some(PREFIX_BONUS + matcher.fuzzy_match(key, query).unwrap_or_default())
+                } else {
+                    matcher.fuzzy_match(key, query)
+                }
+            })
+            .max()
+    }
+}
+
+/**
+ * 联系人缓存，按 wxid、微信号、备注、昵称建立索引，支持模糊和拼音（全拼、首字母）搜索
+ * @example let mut book = ContactBook::new().refresh_interval(Duration::from_secs(600)); book.ensure_fresh(&mut wechat)?; book.search("zs", 5);
+ */
+#[derive(Clone, Debug, Default)]
+pub struct ContactBook {
+    contacts: Vec<Contact>,
+    keys: Vec<SearchKeys>,
+    by_wxid: HashMap<String, usize>,
+    by_code: HashMap<String, usize>,
+    by_remark: HashMap<String, Vec<usize>>,
+    by_name: HashMap<String, Vec<usize>>,
+    interval: Option<Duration>,
+    refreshed_at: Option<Instant>,
+}
+
+impl ContactBook {
+    pub fn new() -> Self {
+        ContactBook::default()
+    }
+
+    /** 用已有的联系人建立缓存，不会自动刷新 */
+    pub fn from_contacts(contacts: Vec<Contact>) -> Self {
+        let mut book = ContactBook::new();
+        book.rebuild(contacts);
+        book
+    }
+
+    /** 超过该时长后 ensure_fresh 会重新获取联系人，未设置时只在首次获取 */
+    pub fn refresh_interval(mut self, interval: Duration) -> Self {
+        self.interval = Some(interval);
+        self
+    }
+
+    fn rebuild(&mut self, contacts: Vec<Contact>) {
+        self.keys = contacts.iter().map(SearchKeys::new).collect();
+        self.by_wxid.clear();
+        self.by_code.clear();
+        self.by_remark.clear();
+        self.by_name.clear();
+        for (i, contact) in contacts.iter().enumerate() {
+            self.by_wxid.insert(contact.wxid.clone(), i);
+            if !contact.code.is_empty() {
+                self.by_code.insert(contact.code.clone(), i);
+            }
+            if !contact.remark.is_empty() {
+                self.by_remark
+                    .entry(contact.remark.clone())
+                    .or_default()
+                    .push(i);
+            }
+            if !contact.name.is_empty() {
+                self.by_name
+                    .entry(contact.name.clone())
+                    .or_default()
+                    .push(i);
+            }
+        }
+        self.contacts = contacts;
+        self.refreshed_at = Some(Instant::now());
+    }
+
+    /** 立即重新获取联系人，获取失败时保留原有缓存 */
+    pub fn refresh(&mut self, wechat: &mut WeChat) -> Result<(), Box<dyn std::error::Error>> {
+        let contacts = contact::get_contact_list(wechat)?;
+        info!("联系人缓存已刷新: {} 个", contacts.len());
+        self.rebuild(contacts);
+        Ok(())
+    }
+
+    /** 从未获取或超过刷新间隔时重新获取联系人 */
+    pub fn ensure_fresh(&mut self, wechat: &mut WeChat) -> Result<(), Box<dyn std::error::Error>> {
+        let stale = match (self.refreshed_at, self.interval) {
+            (None, _) => true,
+            (Some(at), Some(interval)) => at.elapsed() >= interval,
+            (Some(_), None) => false,
+        };
+        if stale {
+            self.refresh(wechat)?;
+        }
+        Ok(())
+    }
+
+    pub fn len(&self) -> usize {
+        self.contacts.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.contacts.is_empty()
+    }
+
+    pub fn contacts(&self) -> &[Contact] {
+        &self.contacts
+    }
+
+    pub fn get(&self, wxid: &str) -> Option<&Contact> {
+        self.by_wxid.get(wxid).map(|&i| &self.contacts[i])
+    }
+
+    /** 按微信号查找 */
+    pub fn by_code(&self, code: &str) -> Option<&Contact> {
+        self.by_code.get(code).map(|&i| &self.contacts[i])
+    }
+
+    /** 按备注查找，备注可能重复 */
+    pub fn by_remark(&self, remark: &str) -> Vec<&Contact> {
+        self.indexed(&self.by_remark, remark)
+    }
+
+    /** 按昵称查找，昵称可能重复 */
+    pub fn by_name(&self, name: &str) -> Vec<&Contact> {
+        self.indexed(&self.by_name, name)
+    }
+
+    fn indexed(&self, index: &HashMap<String, Vec<usize>>, key: &str) -> Vec<&Contact> {
+        index
+            .get(key)
+            .map(|ids| ids.iter().map(|&i| &self.contacts[i]).collect())
+            .unwrap_or_default()
+    }
+
+    /**
+     * 将用户输入解析为唯一的联系人
+     * 依次按 wxid、微信号、备注、昵称完全匹配，备注或昵称重复时视为无法确定
+     */
+    pub fn resolve(&self, input: &str) -> Option<&Contact> {
+        let input = input.trim();
+        if let Some(contact) = self.get(input).or_else(|| self.by_code(input)) {
+            return Some(contact);
+        }
+        for found in [self.by_remark(input), self.by_name(input)] {
+            if found.len() == 1 {
+                return Some(found[0]);
+            }
+        }
+        None
+    }
+
+    /**
+     * 模糊搜索，匹配备注、昵称、微信号、wxid 以及备注和昵称的全拼、首字母
+     * @param query: 搜索内容，不区分大小写，如 "zs" 可找到张三
+     * @param limit: 最多返回的数量
+     * @return 按匹配度从高到低排列
+     */
+    pub fn search(&self, query: &str, limit: usize) -> Vec<&Contact> {
+        let query = query.trim().to_lowercase();
+        if query.is_empty() {
+            return vec![];
+        }
+        let matcher = SkimMatcherV2::default();
+        let mut scored: Vec<(i64, usize)> = self
+            .keys
+            .iter()
+            .enumerate()
+            .filter_map(|(i, keys)| keys.score(&matcher, &query).map(|score| (score, i)))
+            .collect();
+        scored.sort_by(|a, b| {
+            b.0.cmp(&a.0).then_with(|| {
+                self.contacts[a.1]
+                    .display_name()
+                    .cmp(self.contacts[b.1].display_name())
+            })
+        });
+        scored
+            .into_iter()
+            .take(limit)
+            .map(|(_, i)| &self.contacts[i])
+            .collect()
+    }
+}
+
+mod test {
+
+    #[test]
+    fn test_search() {
+        use crate::contact::Contact;
+        use crate::contact_book::ContactBook;
+        use crate::wechat::wcf::RpcContact;
+
+        let contact = |wxid: &str, code: &str, remark: &str, name: &str| {
+            Contact::from(RpcContact {
+                wxid: String::from(wxid),
+                code: String::from(code),
+                remark: String::from(remark),
+                name: String::from(name),
+                ..Default::default()
+            })
+        };
+        let book = ContactBook::from_contacts(vec![
+            contact("wxid_zhangsan", "zs_2020", "", "张三"),
+            contact("wxid_lisi", "", "老李", "李四"),
+            contact("wxid_wangwu", "", "", "王五"),
+            contact("wxid_tom", "tommy", "", "Tom"),
+            contact("wxid_other", "", "", "张三"),
+        ]);
+        assert_eq!(book.len(), 5);
+
+        assert_eq!(book.get("wxid_lisi").unwrap().name, "李四");
+        assert_eq!(book.by_code("tommy").unwrap().wxid, "wxid_tom");
+        assert_eq!(book.by_remark("老李").len(), 1);
+        assert_eq!(book.by_name("张三").len(), 2);
+
+        assert_eq!(book.resolve("zs_2020").unwrap().wxid, "wxid_zhangsan");
+        assert_eq!(book.resolve(" 老李 ").unwrap().wxid, "wxid_lisi");
+        assert_eq!(book.resolve("李四").unwrap().wxid, "wxid_lisi");
+        assert!(book.resolve("张三").is_none());
+
+        let found: Vec<&str> = book
+            .search("zs", 10)
+            .iter()
+            .map(|c| c.wxid.as_str())
+            .collect();
+        assert!(found.contains(&"wxid_zhangsan"));
+        assert!(found.contains(&"wxid_other"));
+        assert_eq!(book.search("laoli", 1)[0].wxid, "wxid_lisi");
+        assert_eq!(book.search("wangwu", 1)[0].wxid, "wxid_wangwu");
+        assert_eq!(book.search("TOM", 1)[0].wxid, "wxid_tom");
+        assert!(book.search("", 10).is_empty());
+        assert_eq!(book.search("zs", 1).len(), 1);
+    }
+
+    #[test]
+    fn test_ensure_fresh() {
+        let mut wechat = crate::wechat::WeChat::default();
+        let mut book = crate::contact_book::ContactBook::new()
+            .refresh_interval(std::time::Duration::from_secs(600));
+        book.ensure_fresh(&mut wechat).unwrap();
+        println!("Found: {:?}", book.search("zs", 5));
+    }
+}
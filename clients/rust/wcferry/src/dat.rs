@@ -0,0 +1,221 @@
+use std::{
+    fs,
+    io::Read,
+    path::{Path, PathBuf},
+};
+
+use log::error;
+
+// 微信图片 .dat 文件本地解密
+// .dat 文件是对原始 JPEG/PNG/GIF/BMP/WebP 文件逐字节异或同一个 key 得到的，
+// 用文件头的魔数即可反推出 key，无需经过服务端 decrypt_image
+
+/** .dat 文件中可识别的原始图片格式 */
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ImageFormat {
+    Jpeg,
+    Png,
+    Gif,
+    Bmp,
+    Webp,
+}
+
+impl ImageFormat {
+    /** 按识别优先级排列，BMP 魔数只有两个字节，放在最后 */
+    const ALL: [ImageFormat; 5] = [
+        ImageFormat::Jpeg,
+        ImageFormat::Png,
+        ImageFormat::Gif,
+        ImageFormat::Webp,
+        ImageFormat::Bmp,
+    ];
+
+    /** 文件扩展名（不带点） */
+    pub fn extension(&self) -> &'static str {
+        match self {
+            ImageFormat::Jpeg => "jpg",
+            ImageFormat::Png => "png",
+            ImageFormat::Gif => "gif",
+            ImageFormat::Bmp => "bmp",
+            ImageFormat::Webp => "webp",
+        }
+    }
+
+    /** (偏移, 魔数) 列表 */
+    fn magic(&self) -> &'static [(usize, &'static [u8])] {
+        match self {
+            ImageFormat::Jpeg => &[(0, &[0xFF, 0xD8, 0xFF])],
+            ImageFormat::Png => &[(0, &[0x89, 0x50, 0x4E, 0x47])],
+            ImageFormat::Gif => &[(0, b"GIF8")],
+            ImageFormat::Bmp => &[(0, b"BM")],
+            ImageFormat::Webp => &[(0, b"RIFF"), (8, b"WEBP")],
+        }
+    }
+
+    fn matches(&self, data: &[u8], key: u8) -> bool {
+        self.magic().iter().all(|(offset, magic)| {
+            data.len() >= offset + magic.len()
+                && data[*offset..offset + magic.len()]
+                    .iter()
+                    .zip(magic.iter())
+                    .all(|(b, m)| b ^ key == *m)
+        })
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct DecryptedImage {
+    pub format: ImageFormat,
+    pub key: u8,
+    pub data: Vec<u8>,
+}
+
+/**
+ * 根据文件头识别图片格式及异或 key
+ * @param data: .dat 文件内容（至少需要文件头部分）
+ * @return 识别失败返回 None
+ */
+pub fn detect(data: &[u8]) -> Option<(ImageFormat, u8)> {
+    if data.is_empty() {
+        return None;
+    }
+    for format in ImageFormat::ALL {
+        let key = data[0] ^ format.magic()[0].1[0];
+        if format.matches(data, key) {
+            return Some((format, key));
+        }
+    }
+    None
+}
+
+/** 解密内存中的 .dat 数据 */
+pub fn decrypt_bytes(data: &[u8]) -> Result<DecryptedImage, Box<dyn std::error::Error>> {
+    let (format, key) = match detect(data) {
+        Some(detected) => detected,
+        None => {
+            error!("无法识别的图片格式, 长度: {}", data.len());
+            return Err("图片解密失败".into());
+        }
+    };
+    Ok(DecryptedImage {
+        format,
+        key,
+        data: data.iter().map(|b| b ^ key).collect(),
+    })
+}
+
+/** 从 reader 读取 .dat 数据并解密 */
+pub fn decrypt_reader<R: Read>(
+    mut reader: R,
+) -> Result<DecryptedImage, Box<dyn std::error::Error>> {
+    let mut data = Vec::new();
+    match reader.read_to_end(&mut data) {
+        Ok(_) => (),
+        Err(e) => {
+            error!("读取图片数据失败: {}", e);
+            return Err("图片解密失败".into());
+        }
+    };
+    decrypt_bytes(&data)
+}
+
+/** 读取 .dat 文件并解密 */
+pub fn decrypt_path<P: AsRef<Path>>(src: P) -> Result<DecryptedImage, Box<dyn std::error::Error>> {
+    let data = match fs::read(src.as_ref()) {
+        Ok(data) => data,
+        Err(e) => {
+            error!("读取文件失败: {}, {}", src.as_ref().display(), e);
+            return Err("图片解密失败".into());
+        }
+    };
+    decrypt_bytes(&data)
+}
+
+/**
+ * 解密 .dat 文件并写入目标位置，对应服务端的 decrypt_image
+ * @param src: .dat 文件路径
+ * @param dst: 目标文件路径；如果是已存在的目录，则在其下生成 `<src 文件名>.<扩展名>`
+ * @return 识别出的格式及实际写入的路径
+ */
+pub fn decrypt_file<P: AsRef<Path>, Q: AsRef<Path>>(
+    src: P,
+    dst: Q,
+) -> Result<(ImageFormat, PathBuf), Box<dyn std::error::Error>> {
+    let src = src.as_ref();
+    let image = decrypt_path(src)?;
+    let dst = if dst.as_ref().is_dir() {
+        // 不用 with_extension，避免文件名中的 . 被当作扩展名替换
+        let stem = src.file_stem().unwrap_or_default().to_string_lossy();
+        dst.as_ref()
+            .join(format!("{}.{}", stem, image.format.extension()))
+    } else {
+        dst.as_ref().to_path_buf()
+    };
+    match fs::write(&dst, &image.data) {
+        Ok(()) => (),
+        Err(e) => {
+            error!("写入文件失败: {}, {}", dst.display(), e);
+            return Err("图片解密失败".into());
+        }
+    };
+    Ok((image.format, dst))
+}
+
+mod test {
+
+    #[test]
+    fn test_detect() {
+        use crate::dat::ImageFormat;
+
+        let encrypt = |data: &[u8], key: u8| data.iter().map(|b| b ^ key).collect::<Vec<u8>>();
+        let cases: [(&[u8], ImageFormat); 5] = [
+            (&[0xFF, 0xD8, 0xFF, 0xE0, 0x00, 0x10], ImageFormat::Jpeg),
+            (&[0x89, 0x50, 0x4E, 0x47, 0x0D, 0x0A], ImageFormat::Png),
+            (b"GIF89a\x01\x00", ImageFormat::Gif),
+            (b"RIFF\x24\x00\x00\x00WEBPVP8 ", ImageFormat::Webp),
+            (b"BM\x36\x00\x0C\x00", ImageFormat::Bmp),
+        ];
+        for (plain, format) in cases {
+            let dat = encrypt(plain, 0x5A);
+            assert_eq!(crate::dat::detect(&dat), Some((format, 0x5A)));
+        }
+        assert_eq!(crate::dat::detect(&[]), None);
+        assert_eq!(crate::dat::detect(&[0x12, 0x34, 0x56, 0x78]), None);
+    }
+
+    #[test]
+    fn test_decrypt_bytes() {
+        let encrypt = |data: &[u8], key: u8| data.iter().map(|b| b ^ key).collect::<Vec<u8>>();
+        let plain = b"\x89PNG\r\n\x1a\n\x00\x00\x00\rIHDR";
+        let image = crate::dat::decrypt_bytes(&encrypt(plain, 0xC3)).unwrap();
+        assert_eq!(image.format, crate::dat::ImageFormat::Png);
+        assert_eq!(image.key, 0xC3);
+        assert_eq!(image.data, plain);
+
+        let image = crate::dat::decrypt_reader(&encrypt(plain, 0x01)[..]).unwrap();
+        assert_eq!(image.data, plain);
+
+        assert!(crate::dat::decrypt_bytes(b"not an image").is_err());
+    }
+
+    #[test]
+    fn test_decrypt_file() {
+        let encrypt = |data: &[u8], key: u8| data.iter().map(|b| b ^ key).collect::<Vec<u8>>();
+        let dir = std::env::temp_dir().join("wcferry_test_dat");
+        let _ = std::fs::create_dir_all(&dir);
+        let src = dir.join("c66044e188c64452e236e53eff73324b.dat");
+        let plain = b"\xFF\xD8\xFF\xE0\x00\x10JFIF\x00";
+        std::fs::write(&src, encrypt(plain, 0x9E)).unwrap();
+
+        let (format, dst) = crate::dat::decrypt_file(&src, &dir).unwrap();
+        assert_eq!(format, crate::dat::ImageFormat::Jpeg);
+        assert_eq!(dst, dir.join("c66044e188c64452e236e53eff73324b.jpg"));
+        assert_eq!(std::fs::read(&dst).unwrap(), plain);
+
+        let src = dir.join("thumb.v2.dat");
+        std::fs::write(&src, encrypt(plain, 0x9E)).unwrap();
+        let (_, dst) = crate::dat::decrypt_file(&src, &dir).unwrap();
+        assert_eq!(dst, dir.join("thumb.v2.jpg"));
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,367 @@
+use std::{
+    path::PathBuf,
+    sync::{mpsc, Arc, Mutex},
+    thread,
+    time::Duration,
+};
+
+use log::{error, warn};
+
+use crate::{
+    error::WcfError,
+    receiver::{Receiver, Wxid},
+    status::SendStatus,
+    wechat::{self, wcf, WeChat},
+    xmlmsg::XmlMessage,
+};
+
+/** 文字 */
+const ECHO_TYPE_TEXT: u32 = 1;
+/** 图片 */
+const ECHO_TYPE_IMAGE: u32 = 3;
+/** 表情 */
+const ECHO_TYPE_EMOTION: u32 = 47;
+/** 文件、链接、小程序等 appmsg */
+const ECHO_TYPE_APP: u32 = 49;
+
+/** 默认等待回显的时间 */
+pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/** 已确认发出的消息 */
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SentMessage {
+    /** 消息 id，可用于引用、撤回 */
+    pub id: u64,
+    pub ts: u32,
+}
+
+#[derive(Debug)]
+struct Waiter {
+    token: u64,
+    receiver: String,
+    msg_type: u32,
+    /** 文字消息按内容匹配，其他类型只按接收人和类型匹配 */
+    content: Option<String>,
+    tx: mpsc::Sender<SentMessage>,
+}
+
+impl Waiter {
+    fn matches(&self, msg: &wcf::WxMsg) -> bool {
+        let content_matches = match &self.content {
+            Some(content) => *content == msg.content,
+            None => true,
+        };
+        msg.roomid == self.receiver && msg.r#type == self.msg_type && content_matches
+    }
+}
+
+#[derive(Debug, Default)]
+struct Waiters {
+    next_token: u64,
+    list: Vec<Waiter>,
+}
+
+/**
+ * 通过自己发出消息的回显确认发送结果
+ * 发送前登记，消息流中收到匹配的 is_self 消息即视为发送成功，并取得消息 id
+ * @example let (echo, messages) = EchoTracker::listen(&mut wechat)?; let sent = echo.send_text(&mut wechat, "hi".into(), receiver, &[])?;
+ */
+#[derive(Clone, Debug)]
+pub struct EchoTracker {
+    waiters: Arc<Mutex<Waiters>>,
+    timeout: Duration,
+}
+
+impl Default for EchoTracker {
+    fn default() -> Self {
+        EchoTracker::new(DEFAULT_TIMEOUT)
+    }
+}
+
+impl EchoTracker {
+    /**
+     * 创建后需将收到的每条消息交给 observe
+     * @param timeout: 等待回显的时间
+     */
+    pub fn new(timeout: Duration) -> Self {
+        EchoTracker {
+            waiters: Arc::new(Mutex::new(Waiters::default())),
+            timeout,
+        }
+    }
+
+    /**
+     * 开启消息接收并在后台线程中读取消息
+     * 所有消息（包括回显）原样转发到返回的通道，通道关闭后线程在收到下一条消息时退出
+     */
+    pub fn listen(
+        wechat: &mut WeChat,
+    ) -> Result<(EchoTracker, mpsc::Receiver<wcf::WxMsg>), Box<dyn std::error::Error>> {
+        let socket = wechat::enable_listen(wechat)?;
+        let tracker = EchoTracker::default();
+        let (tx, rx) = mpsc::channel();
+        let observer = tracker.clone();
+        let wechat = wechat.clone();
+        thread::spawn(move || loop {
+            match wechat::recv_msg(&wechat, &socket) {
+                Ok(Some(msg)) => {
+                    observer.observe(&msg);
+                    if tx.send(msg).is_err() {
+                        break;
+                    }
+                }
+                Ok(None) => {}
+                Err(e) => warn!("回显监听收到无效消息: {}", e),
+            }
+        });
+        Ok((tracker, rx))
+    }
+
+    /**
+     * 处理收到的消息
+     * @return 是否匹配到等待中的发送
+     */
+    pub fn observe(&self, msg: &wcf::WxMsg) -> bool {
+        if !msg.is_self {
+            return false;
+        }
+        let mut waiters = self.waiters.lock().unwrap();
+        match waiters.list.iter().position(|w| w.matches(msg)) {
+            Some(index) => {
+                let waiter = waiters.list.remove(index);
+                let _ = waiter.tx.send(SentMessage {
+                    id: msg.id,
+                    ts: msg.ts,
+                });
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn register(
+        &self,
+        receiver: &Receiver,
+        msg_type: u32,
+        content: Option<String>,
+    ) -> (u64, mpsc::Receiver<SentMessage>) {
+        let (tx, rx) = mpsc::channel();
+        let mut waiters = self.waiters.lock().unwrap();
+        waiters.next_token += 1;
+        let token = waiters.next_token;
+        waiters.list.push(Waiter {
+            token,
+            receiver: receiver.to_string(),
+            msg_type,
+            content,
+            tx,
+        });
+        (token, rx)
+    }
+
+    fn unregister(&self, token: u64) {
+        self.waiters
+            .lock()
+            .unwrap()
+            .list
+            .retain(|w| w.token != token);
+    }
+
+    /** 先登记再发送，避免回显先于登记到达 */
+    fn confirm<F>(
+        &self,
+        receiver: Receiver,
+        msg_type: u32,
+        content: Option<String>,
+        send: F,
+    ) -> Result<SentMessage, WcfError>
+    where
+        F: FnOnce(Receiver) -> Result<SendStatus, WcfError>,
+    {
+        let (token, rx) = self.register(&receiver, msg_type, content);
+        match send(receiver) {
+            Ok(SendStatus::Success) => {}
+            Ok(status) => {
+                self.unregister(token);
+                return Err(WcfError::Other(format!("消息发送失败: {}", status)));
+            }
+            Err(e) => {
+                self.unregister(token);
+                return Err(e);
+            }
+        }
+        match rx.recv_timeout(self.timeout) {
+            Ok(sent) => Ok(sent),
+            Err(_) => {
+                self.unregister(token);
+                error!("等待发送回显超时");
+                Err(WcfError::Timeout(String::from("发送确认超时")))
+            }
+        }
+    }
+
+    pub fn send_text(
+        &self,
+        wechat: &mut WeChat,
+        msg: String,
+        receiver: impl Into<Receiver>,
+        aters: &[Wxid],
+    ) -> Result<SentMessage, WcfError> {
+        // 回显的是过滤后实际发出的内容
+        let msg = wechat::filter_text(wechat.word_filter.as_deref(), msg)?;
+        let content = Some(msg.clone());
+        self.confirm(receiver.into(), ECHO_TYPE_TEXT, content, |receiver| {
+            wechat::send_filtered_text(wechat, msg, receiver, aters)
+        })
+    }
+
+    pub fn send_image(
+        &self,
+        wechat: &mut WeChat,
+        path: PathBuf,
+        receiver: impl Into<Receiver>,
+    ) -> Result<SentMessage, WcfError> {
+        self.confirm(receiver.into(), ECHO_TYPE_IMAGE, None, |receiver| {
+            wechat::send_image(wechat, path, receiver)
+        })
+    }
+
+    pub fn send_file(
+        &self,
+        wechat: &mut WeChat,
+        path: PathBuf,
+        receiver: impl Into<Receiver>,
+    ) -> Result<SentMessage, WcfError> {
+        self.confirm(receiver.into(), ECHO_TYPE_APP, None, |receiver| {
+            wechat::send_file(wechat, path, receiver)
+        })
+    }
+
+    pub fn send_xml(
+        &self,
+        wechat: &mut WeChat,
+        xml: &XmlMessage,
+        receiver: impl Into<Receiver>,
+    ) -> Result<SentMessage, WcfError> {
+        self.confirm(receiver.into(), ECHO_TYPE_APP, None, |receiver| {
+            xml.send(wechat, receiver)
+        })
+    }
+
+    pub fn send_emotion(
+        &self,
+        wechat: &mut WeChat,
+        path: PathBuf,
+        receiver: impl Into<Receiver>,
+    ) -> Result<SentMessage, WcfError> {
+        self.confirm(receiver.into(), ECHO_TYPE_EMOTION, None, |receiver| {
+            wechat::send_emotion(wechat, path, receiver)
+        })
+    }
+}
+
+mod test {
+
+    #[test]
+    fn test_observe() {
+        use std::time::Duration;
+
+        use crate::echo::{EchoTracker, SentMessage};
+        use crate::receiver::Receiver;
+        use crate::status::SendStatus;
+        use crate::wechat::wcf::WxMsg;
+
+        let tracker = EchoTracker::new(Duration::from_millis(500));
+        let receiver: Receiver = "34476879773@chatroom".parse().unwrap();
+        let echo = WxMsg {
+            is_self: true,
+            is_group: true,
+            id: 42,
+            r#type: 1,
+            ts: 1700000000,
+            roomid: String::from("34476879773@chatroom"),
+            content: String::from("hello"),
+            ..Default::default()
+        };
+
+        let observer = tracker.clone();
+        let other = WxMsg {
+            content: String::from("other"),
+            ..echo.clone()
+        };
+        let incoming = WxMsg {
+            is_self: false,
+            ..echo.clone()
+        };
+        let sent = tracker
+            .confirm(receiver.clone(), 1, Some(String::from("hello")), |_| {
+                assert!(!observer.observe(&other));
+                assert!(!observer.observe(&incoming));
+                assert!(observer.observe(&echo));
+                Ok(SendStatus::Success)
+            })
+            .unwrap();
+        assert_eq!(
+            sent,
+            SentMessage {
+                id: 42,
+                ts: 1700000000
+            }
+        );
+
+        assert!(tracker
+            .confirm(receiver.clone(), 3, None, |_| Ok(SendStatus::Success))
+            .is_err());
+        assert!(tracker
+            .confirm(receiver, 3, None, |_| Ok(SendStatus::Unknown(-1)))
+            .is_err());
+        assert!(tracker.waiters.lock().unwrap().list.is_empty());
+    }
+
+    #[test]
+    fn test_observe_masked() {
+        use std::time::Duration;
+
+        use crate::echo::EchoTracker;
+        use crate::filter::WordFilter;
+        use crate::receiver::Receiver;
+        use crate::status::SendStatus;
+        use crate::wechat::{self, wcf::WxMsg};
+
+        let filter = WordFilter::parse("mask 傻瓜").unwrap();
+        let msg = wechat::filter_text(Some(&filter), String::from("你个傻瓜")).unwrap();
+        assert_eq!(msg, "你个**");
+
+        let tracker = EchoTracker::new(Duration::from_millis(500));
+        let receiver: Receiver = "wxid_abc".parse().unwrap();
+        let echo = WxMsg {
+            is_self: true,
+            id: 42,
+            r#type: 1,
+            roomid: String::from("wxid_abc"),
+            content: msg.clone(),
+            ..Default::default()
+        };
+        let observer = tracker.clone();
+        let sent = tracker.confirm(receiver, 1, Some(msg), |_| {
+            assert!(observer.observe(&echo));
+            Ok(SendStatus::Success)
+        });
+        assert_eq!(sent.unwrap().id, 42);
+    }
+
+    #[test]
+    fn test_send_text() {
+        let mut wechat = crate::wechat::WeChat::default();
+        let (echo, _messages) = crate::echo::EchoTracker::listen(&mut wechat).unwrap();
+        let sent = echo
+            .send_text(
+                &mut wechat,
+                String::from("Hello, wcferry!"),
+                crate::receiver::SpecialAccount::FileHelper,
+                &[],
+            )
+            .unwrap();
+        println!("Sent: {:?}", sent);
+    }
+}
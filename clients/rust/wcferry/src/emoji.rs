@@ -0,0 +1,294 @@
+use std::borrow::Cow;
+
+// 微信内置表情的文字代码（如 [微笑]、[Smile]）与 Unicode emoji 的对应关系
+// 微信表情没有一一对应的 Unicode，取含义最接近的；多个代码对应同一 emoji 时，反向转换按 PREFERRED 选取
+
+/** (中文代码, 英文代码, Unicode) */
+const EMOJIS: &[(&str, &str, &str)] = &[
+    ("微笑", "Smile", "🙂"),
+    ("撇嘴", "Grimace", "😖"),
+    ("色", "Drool", "😍"),
+    ("发呆", "Scowl", "😳"),
+    ("得意", "CoolGuy", "😎"),
+    ("流泪", "Sob", "😢"),
+    ("害羞", "Shy", "☺️"),
+    ("闭嘴", "Silent", "🤐"),
+    ("睡", "Sleep", "😴"),
+    ("大哭", "Cry", "😭"),
+    ("尴尬", "Awkward", "😓"),
+    ("发怒", "Angry", "😡"),
+    ("调皮", "Tongue", "😜"),
+    ("呲牙", "Grin", "😁"),
+    ("惊讶", "Surprise", "😲"),
+    ("难过", "Frown", "🙁"),
+    ("囧", "Blush", "😅"),
+    ("抓狂", "Scream", "😫"),
+    ("吐", "Puke", "🤮"),
+    ("偷笑", "Chuckle", "🤭"),
+    ("愉快", "Joyful", "😊"),
+    ("白眼", "Slight", "🙄"),
+    ("傲慢", "Smug", "😤"),
+    ("困", "Drowsy", "😪"),
+    ("惊恐", "Panic", "😱"),
+    ("憨笑", "Laugh", "😄"),
+    ("悠闲", "Commando", "😌"),
+    ("咒骂", "Scold", "🤬"),
+    ("疑问", "Shocked", "❓"),
+    ("嘘", "Shhh", "🤫"),
+    ("晕", "Dizzy", "😵"),
+    ("衰", "Toasted", "😩"),
+    ("骷髅", "Skull", "💀"),
+    ("敲打", "Hammer", "🔨"),
+    ("再见", "Wave", "👋"),
+    ("擦汗", "Speechless", "😥"),
+    ("抠鼻", "NosePick", "👃"),
+    ("鼓掌", "Clap", "👏"),
+    ("坏笑", "Trick", "😏"),
+    ("哈欠", "Yawn", "🥱"),
+    ("鄙视", "Pooh-pooh", "😒"),
+    ("委屈", "Shrunken", "🥺"),
+    ("快哭了", "TearingUp", "😿"),
+    ("阴险", "Sly", "😈"),
+    ("亲亲", "Kiss", "😘"),
+    ("可怜", "Whimper", "😞"),
+    ("笑脸", "Happy", "😃"),
+    ("生病", "Sick", "😷"),
+    ("破涕为笑", "Lol", "😂"),
+    ("脸红", "Flushed", "😳"),
+    ("恐惧", "Terror", "😨"),
+    ("失望", "LetDown", "😔"),
+    ("无语", "Duh", "😑"),
+    ("嘿哈", "Hey", "😆"),
+    ("捂脸", "Facepalm", "🤦"),
+    ("奸笑", "Smirk", "😼"),
+    ("机智", "Smart", "🤓"),
+    ("皱眉", "Concerned", "😟"),
+    ("耶", "Yeah!", "✌️"),
+    ("吃瓜", "Onlooker", "🍉"),
+    ("加油", "GoForIt", "💪"),
+    ("汗", "Sweats", "😰"),
+    ("天啊", "OMG", "🙀"),
+    ("Emm", "Emm", "🤔"),
+    ("社会社会", "Respect", "🙏"),
+    ("旺柴", "Doge", "🐶"),
+    ("好的", "NoProb", "👌"),
+    ("打脸", "MyBad", "🤕"),
+    ("哇", "Wow", "😮"),
+    ("翻白眼", "Boring", "🙄"),
+    ("666", "Awesome", "👍"),
+    ("让我看看", "LetMeSee", "👀"),
+    ("叹气", "Sigh", "😮‍💨"),
+    ("苦涩", "Hurt", "😣"),
+    ("裂开", "Broken", "💔"),
+    ("菜刀", "Cleaver", "🔪"),
+    ("西瓜", "Watermelon", "🍉"),
+    ("啤酒", "Beer", "🍺"),
+    ("咖啡", "Coffee", "☕"),
+    ("猪头", "Pig", "🐷"),
+    ("玫瑰", "Rose", "🌹"),
+    ("凋谢", "Wilt", "🥀"),
+    ("嘴唇", "Lips", "💋"),
+    ("爱心", "Heart", "❤️"),
+    ("心碎", "BrokenHeart", "💔"),
+    ("蛋糕", "Cake", "🎂"),
+    ("炸弹", "Bomb", "💣"),
+    ("便便", "Poop", "💩"),
+    ("月亮", "Moon", "🌙"),
+    ("太阳", "Sun", "☀️"),
+    ("拥抱", "Hug", "🤗"),
+    ("强", "ThumbsUp", "👍"),
+    ("弱", "ThumbsDown", "👎"),
+    ("握手", "Shake", "🤝"),
+    ("胜利", "Peace", "✌️"),
+    ("抱拳", "Fight", "🙏"),
+    ("勾引", "Beckon", "🫴"),
+    ("拳头", "Fist", "✊"),
+    ("OK", "OK", "👌"),
+    ("跳跳", "Waddle", "💃"),
+    ("发抖", "Tremble", "🥶"),
+    ("怄火", "Aaagh!", "😠"),
+    ("转圈", "Twirl", "🕺"),
+    ("合十", "Worship", "🙏"),
+    ("庆祝", "Party", "🎉"),
+    ("礼物", "Gift", "🎁"),
+    ("红包", "Packet", "🧧"),
+    ("發", "Rich", "💰"),
+    ("福", "Blessing", "🈵"),
+    ("烟花", "Fireworks", "🎆"),
+    ("爆竹", "Firecracker", "🧨"),
+];
+
+/** 多个代码对应同一 emoji 时，反向转换使用的中文代码，优先经典表情 */
+const PREFERRED: &[(&str, &str)] = &[
+    ("👍", "强"),
+    ("👌", "OK"),
+    ("🙏", "抱拳"),
+    ("🍉", "西瓜"),
+    ("✌️", "胜利"),
+    ("💔", "心碎"),
+    ("😳", "脸红"),
+    ("🙄", "白眼"),
+];
+
+/** 文字代码最长的字符数，超过的方括号内容不视为表情 */
+const MAX_CODE_CHARS: usize = 12;
+
+/** 转回文字代码时使用的语言 */
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Lang {
+    #[default]
+    Zh,
+    En,
+}
+
+/**
+ * 查找文字代码对应的 emoji
+ * @param code: 方括号内的文字，中文完全匹配，英文不区分大小写
+ */
+pub fn lookup(code: &str) -> Option<&'static str> {
+    EMOJIS
+        .iter()
+        .find(|(zh, _, _)| *zh == code)
+        .or_else(|| {
+            EMOJIS
+                .iter()
+                .find(|(_, en, _)| en.eq_ignore_ascii_case(code))
+        })
+        .map(|(_, _, emoji)| *emoji)
+}
+
+/** 查找 emoji 对应的文字代码（不含方括号） */
+pub fn shortcode(emoji: &str, lang: Lang) -> Option<&'static str> {
+    let preferred = PREFERRED
+        .iter()
+        .find(|(e, _)| *e == emoji)
+        .and_then(|(_, code)| EMOJIS.iter().find(|(zh, _, _)| zh == code));
+    preferred
+        .or_else(|| EMOJIS.iter().find(|(_, _, e)| *e == emoji))
+        .map(|(zh, en, _)| match lang {
+            Lang::Zh => *zh,
+            Lang::En => *en,
+        })
+}
+
+/** 将文本中的 [微笑]、[Smile] 等替换为 emoji，无法识别的保持原样 */
+pub fn to_unicode(text: &str) -> Cow<'_, str> {
+    if !text.contains('[') {
+        return Cow::Borrowed(text);
+    }
+    let mut result = String::with_capacity(text.len());
+    let mut rest = text;
+    let mut changed = false;
+    while let Some(start) = rest.find('[') {
+        result.push_str(&rest[..start]);
+        let after = &rest[start + 1..];
+        let emoji = after
+            .find([']', '['])
+            .filter(|&end| after[end..].starts_with(']'))
+            .filter(|&end| after[..end].chars().count() <= MAX_CODE_CHARS)
+            .and_then(|end| lookup(&after[..end]).map(|emoji| (end, emoji)));
+        match emoji {
+            Some((end, emoji)) => {
+                result.push_str(emoji);
+                rest = &after[end + 1..];
+                changed = true;
+            }
+            None => {
+                result.push('[');
+                rest = after;
+            }
+        }
+    }
+    if !changed {
+        return Cow::Borrowed(text);
+    }
+    result.push_str(rest);
+    Cow::Owned(result)
+}
+
+/** 将文本中的 emoji 替换为 [微笑] 或 [Smile] 形式的文字代码，表中没有的保持原样 */
+pub fn to_shortcode(text: &str, lang: Lang) -> Cow<'_, str> {
+    if text.is_ascii() {
+        return Cow::Borrowed(text);
+    }
+    let mut result = String::with_capacity(text.len());
+    let mut changed = false;
+    let mut i = 0;
+    while i < text.len() {
+        // 同一位置取最长匹配，如 😮‍💨 优先于 😮
+        let matched = EMOJIS
+            .iter()
+            .map(|(_, _, emoji)| *emoji)
+            .filter(|emoji| text[i..].starts_with(emoji))
+            .max_by_key(|emoji| emoji.len())
+            .and_then(|emoji| shortcode(emoji, lang).map(|code| (code, emoji)));
+        match matched {
+            Some((code, emoji)) => {
+                result.push('[');
+                result.push_str(code);
+                result.push(']');
+                i += emoji.len();
+                changed = true;
+            }
+            None => {
+                let c = text[i..].chars().next().unwrap_or_default();
+                result.push(c);
+                i += c.len_utf8();
+            }
+        }
+    }
+    if changed {
+        Cow::Owned(result)
+    } else {
+        Cow::Borrowed(text)
+    }
+}
+
+mod test {
+
+    #[test]
+    fn test_to_unicode() {
+        use crate::emoji::to_unicode;
+
+        assert_eq!(to_unicode("你好[微笑]"), "你好🙂");
+        assert_eq!(to_unicode("[Smile][捂脸]"), "🙂🤦");
+        assert_eq!(to_unicode("[smile]"), "🙂");
+        assert_eq!(to_unicode("[[微笑]]"), "[🙂]");
+        assert_eq!(to_unicode("[不存在] [微笑"), "[不存在] [微笑");
+        assert!(matches!(
+            to_unicode("no emoji"),
+            std::borrow::Cow::Borrowed(_)
+        ));
+    }
+
+    #[test]
+    fn test_preferred() {
+        use crate::emoji::{lookup, EMOJIS, PREFERRED};
+
+        // 每个优先代码都存在且与 emoji 对应，每个重复的 emoji 都指定了优先代码
+        for (emoji, code) in PREFERRED {
+            assert_eq!(lookup(code), Some(*emoji));
+        }
+        for (_, _, emoji) in EMOJIS {
+            let count = EMOJIS.iter().filter(|(_, _, e)| e == emoji).count();
+            if count > 1 {
+                assert!(PREFERRED.iter().any(|(e, _)| e == emoji), "{}", emoji);
+            }
+        }
+    }
+
+    #[test]
+    fn test_to_shortcode() {
+        use crate::emoji::{to_shortcode, to_unicode, Lang};
+
+        assert_eq!(to_shortcode("你好🙂", Lang::Zh), "你好[微笑]");
+        assert_eq!(to_shortcode("你好🙂", Lang::En), "你好[Smile]");
+        // 多个代码对应同一 emoji 时取经典表情
+        assert_eq!(to_shortcode("👍👌🙏", Lang::Zh), "[强][OK][抱拳]");
+        assert_eq!(to_shortcode("🍉✌️💔", Lang::Zh), "[西瓜][胜利][心碎]");
+        assert_eq!(to_shortcode("👍", Lang::En), "[ThumbsUp]");
+        assert_eq!(to_shortcode("😮‍💨😮", Lang::Zh), "[叹气][哇]");
+        assert_eq!(to_shortcode("🦀", Lang::Zh), "🦀");
+        assert_eq!(to_unicode(&to_shortcode("🙂🤦", Lang::Zh)), "🙂🤦");
+    }
+}
@@ -0,0 +1,49 @@
+use std::{error::Error, fmt};
+
+/** 调用服务端接口时的错误，服务端明确返回的失败状态见 SendStatus */
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum WcfError {
+    /** 参数无效，如私聊 @、路径不是有效的 UTF-8 */
+    InvalidArgument(String),
+    /** 超过发送速率，仅在限流器为 FailFast 模式时出现 */
+    RateLimited,
+    /** 内容命中敏感词过滤的 block 规则，值为命中的词 */
+    Blocked(String),
+    /** 请求未能发出，如连接断开，可重试 */
+    Transport(String),
+    /** 服务端响应为空或类型不符 */
+    Protocol(String),
+    /** 等待结果超时，消息可能已经发出，不应直接重试 */
+    Timeout(String),
+    /** 其他错误，如下载、读取文件失败 */
+    Other(String),
+}
+
+impl WcfError {
+    /** 稍后重试可能成功 */
+    pub fn is_retryable(&self) -> bool {
+        matches!(self, WcfError::RateLimited | WcfError::Transport(_))
+    }
+}
+
+impl fmt::Display for WcfError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            WcfError::InvalidArgument(msg) => write!(f, "参数无效: {}", msg),
+            WcfError::RateLimited => write!(f, "发送过于频繁"),
+            WcfError::Blocked(word) => write!(f, "内容包含敏感词: {}", word),
+            WcfError::Transport(msg) => write!(f, "通信失败: {}", msg),
+            WcfError::Protocol(msg) => write!(f, "响应无效: {}", msg),
+            WcfError::Timeout(msg) => write!(f, "超时: {}", msg),
+            WcfError::Other(msg) => write!(f, "{}", msg),
+        }
+    }
+}
+
+impl Error for WcfError {}
+
+impl From<Box<dyn Error>> for WcfError {
+    fn from(e: Box<dyn Error>) -> Self {
+        WcfError::Other(e.to_string())
+    }
+}
@@ -0,0 +1,265 @@
+use std::{borrow::Cow, fs, ops::Range, path::Path};
+
+use aho_corasick::{AhoCorasick, AhoCorasickBuilder, MatchKind};
+use log::{error, warn};
+
+use crate::{error::WcfError, xmlmsg::escape};
+
+/** 遮盖敏感词时使用的字符 */
+const MASK_CHAR: char = '*';
+
+/** 命中敏感词后的处理方式 */
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum FilterAction {
+    /** 拒绝发送，返回 WcfError::Blocked */
+    #[default]
+    Block,
+    /** 将敏感词逐字替换为 * 后发送 */
+    Mask,
+    /** 仅记录日志，照常发送 */
+    Log,
+}
+
+impl FilterAction {
+    fn parse(s: &str) -> Option<FilterAction> {
+        match s {
+            "block" => Some(FilterAction::Block),
+            "mask" => Some(FilterAction::Mask),
+            "log" => Some(FilterAction::Log),
+            _ => None,
+        }
+    }
+}
+
+/** 一条过滤规则 */
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Rule {
+    pub word: String,
+    pub action: FilterAction,
+}
+
+impl Rule {
+    pub fn new(word: &str, action: FilterAction) -> Self {
+        Rule {
+            word: word.to_string(),
+            action,
+        }
+    }
+}
+
+/**
+ * 发送前的敏感词过滤，多模式匹配，英文不区分大小写
+ * 互相重叠的词都会被检查，如 log evilcorp.com 不会掩盖 block evil
+ * @example wechat.word_filter = Some(Arc::new(WordFilter::load("words.txt")?));
+ */
+#[derive(Clone, Debug)]
+pub struct WordFilter {
+    matcher: AhoCorasick,
+    rules: Vec<Rule>,
+}
+
+impl WordFilter {
+    pub fn new(rules: Vec<Rule>) -> Result<WordFilter, Box<dyn std::error::Error>> {
+        let rules: Vec<Rule> = rules.into_iter().filter(|r| !r.word.is_empty()).collect();
+        let matcher = match AhoCorasickBuilder::new()
+            .ascii_case_insensitive(true)
+            .match_kind(MatchKind::Standard)
+            .build(rules.iter().map(|r| &r.word))
+        {
+            Ok(matcher) => matcher,
+            Err(e) => {
+                error!("敏感词表构建失败: {}", e);
+                return Err("敏感词表构建失败".into());
+            }
+        };
+        Ok(WordFilter { matcher, rules })
+    }
+
+    /**
+     * 解析词表，每行一条规则，# 开头为注释
+     * `block 词`、`mask 词`、`log 词` 指定处理方式，只写词时为 block
+     */
+    pub fn parse(list: &str) -> Result<WordFilter, Box<dyn std::error::Error>> {
+        let rules = list
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty() && !line.starts_with('#'))
+            .map(|line| {
+                let action = line
+                    .split_once(char::is_whitespace)
+                    .and_then(|(action, word)| Some((FilterAction::parse(action)?, word.trim())));
+                match action {
+                    Some((action, word)) => Rule::new(word, action),
+                    None => Rule::new(line, FilterAction::default()),
+                }
+            })
+            .collect();
+        WordFilter::new(rules)
+    }
+
+    /** 从文件加载词表，格式见 parse */
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<WordFilter, Box<dyn std::error::Error>> {
+        let list = match fs::read_to_string(path.as_ref()) {
+            Ok(list) => list,
+            Err(e) => {
+                error!("读取敏感词表失败: {}, {}", path.as_ref().display(), e);
+                return Err("读取敏感词表失败".into());
+            }
+        };
+        WordFilter::parse(&list)
+    }
+
+    pub fn rules(&self) -> &[Rule] {
+        &self.rules
+    }
+
+    /**
+     * 过滤文本
+     * @return 需要遮盖时返回遮盖后的文本，命中 block 规则时返回 WcfError::Blocked
+     */
+    pub fn apply<'a>(&self, text: &'a str) -> Result<Cow<'a, str>, WcfError> {
+        let mut masks: Vec<Range<usize>> = vec![];
+        for m in self.matcher.find_overlapping_iter(text) {
+            let rule = &self.rules[m.pattern().as_usize()];
+            let word = &text[m.range()];
+            match rule.action {
+                FilterAction::Block => {
+                    warn!("消息包含敏感词，已拒绝发送: {}", word);
+                    return Err(WcfError::Blocked(rule.word.clone()));
+                }
+                FilterAction::Mask => masks.push(m.range()),
+                FilterAction::Log => warn!("消息包含敏感词: {}", word),
+            }
+        }
+        if masks.is_empty() {
+            return Ok(Cow::Borrowed(text));
+        }
+        // 重叠的遮盖范围合并处理
+        masks.sort_by_key(|range| range.start);
+        let mut masked = String::with_capacity(text.len());
+        let mut last = 0;
+        for range in masks {
+            if range.end <= last {
+                continue;
+            }
+            let start = range.start.max(last);
+            masked.push_str(&text[last..start]);
+            masked.extend(text[start..range.end].chars().map(|_| MASK_CHAR));
+            last = range.end;
+        }
+        masked.push_str(&text[last..]);
+        Ok(Cow::Owned(masked))
+    }
+
+    /**
+     * 过滤 XML 中的文本节点（含 CDATA），标签和属性保持不变
+     * XML 无法解析时按普通文本过滤
+     */
+    pub fn apply_xml<'a>(&self, xml: &'a str) -> Result<Cow<'a, str>, WcfError> {
+        let doc = match roxmltree::Document::parse(xml) {
+            Ok(doc) => doc,
+            Err(e) => {
+                warn!("XML 解析失败，按文本过滤: {}", e);
+                return self.apply(xml);
+            }
+        };
+        let mut replacements = vec![];
+        for node in doc.descendants().filter(|n| n.is_text()) {
+            let text = node.text().unwrap_or_default();
+            let filtered = match self.apply(text)? {
+                Cow::Borrowed(_) => continue,
+                Cow::Owned(filtered) => filtered,
+            };
+            let range = node.range();
+            let raw = &xml[range.clone()];
+            let next = &xml[range.end..];
+            // 文本与 CDATA 混排时 range 只覆盖第一段，无法安全替换
+            let complete =
+                next.is_empty() || (next.starts_with('<') && !next.starts_with("<![CDATA["));
+            let replacement = if !complete {
+                None
+            } else if raw.starts_with("<![CDATA[") {
+                Some(format!("<![CDATA[{}]]>", filtered))
+            } else {
+                Some(escape(&filtered))
+            };
+            match replacement {
+                Some(replacement) => replacements.push((range, replacement)),
+                None => {
+                    error!("无法遮盖 XML 中的敏感词: {}", text);
+                    return Err(WcfError::Blocked(String::from(text)));
+                }
+            }
+        }
+        if replacements.is_empty() {
+            return Ok(Cow::Borrowed(xml));
+        }
+        let mut out = xml.to_string();
+        for (range, replacement) in replacements.into_iter().rev() {
+            out.replace_range(range, &replacement);
+        }
+        Ok(Cow::Owned(out))
+    }
+}
+
+mod test {
+
+    #[test]
+    fn test_apply() {
+        use std::borrow::Cow;
+
+        use crate::error::WcfError;
+        use crate::filter::WordFilter;
+
+        let filter = WordFilter::parse(
+            "# 词表\n赌博\nmask 傻瓜\nmask bad\nlog example.com\nblock http://evil.com\n",
+        )
+        .unwrap();
+        assert_eq!(filter.rules().len(), 5);
+
+        assert!(matches!(filter.apply("你好"), Ok(Cow::Borrowed("你好"))));
+        assert_eq!(filter.apply("你个傻瓜").unwrap(), "你个**");
+        assert_eq!(filter.apply("BAD 傻瓜 bad").unwrap(), "*** ** ***");
+        assert_eq!(filter.apply("see example.com").unwrap(), "see example.com");
+        assert_eq!(
+            filter.apply("傻瓜去赌博"),
+            Err(WcfError::Blocked(String::from("赌博")))
+        );
+        assert!(filter.apply("点击 HTTP://EVIL.COM/x").is_err());
+    }
+
+    #[test]
+    fn test_apply_overlapping() {
+        use crate::error::WcfError;
+        use crate::filter::WordFilter;
+
+        let filter = WordFilter::parse("log evilcorp.com\nblock evil\n").unwrap();
+        assert_eq!(
+            filter.apply("visit evilcorp.com"),
+            Err(WcfError::Blocked(String::from("evil")))
+        );
+
+        let filter = WordFilter::parse("mask ab\nmask bcd\nlog abcdef\n").unwrap();
+        assert_eq!(filter.apply("xabcdefx").unwrap(), "x****efx");
+    }
+
+    #[test]
+    fn test_apply_xml() {
+        use crate::filter::{FilterAction, Rule, WordFilter};
+
+        let filter = WordFilter::new(vec![
+            Rule::new("傻瓜", FilterAction::Mask),
+            Rule::new("赌博", FilterAction::Block),
+        ])
+        .unwrap();
+        let xml = r#"<msg><appmsg title="傻瓜"><title>傻瓜 &amp; 朋友</title><des><![CDATA[<傻瓜>]]></des></appmsg></msg>"#;
+        assert_eq!(
+            filter.apply_xml(xml).unwrap(),
+            r#"<msg><appmsg title="傻瓜"><title>** &amp; 朋友</title><des><![CDATA[<**>]]></des></appmsg></msg>"#
+        );
+        assert!(filter.apply_xml("<msg><title>赌博</title></msg>").is_err());
+        assert!(filter
+            .apply_xml("<msg><title>a<![CDATA[傻瓜]]>b</title></msg>")
+            .is_err());
+    }
+}
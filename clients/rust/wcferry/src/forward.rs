@@ -0,0 +1,302 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use log::{error, warn};
+
+use crate::{
+    dat,
+    error::WcfError,
+    media::{self, Media},
+    message::{
+        self, AppMessage, Message, MSG_TYPE_APP, MSG_TYPE_EMOJI, MSG_TYPE_IMAGE, MSG_TYPE_TEXT,
+    },
+    receiver::Receiver,
+    status::SendStatus,
+    wechat::{self, wcf, WeChat},
+    xmlmsg::{XmlMessage, XML_TYPE_APP, XML_TYPE_MINI_PROGRAM},
+};
+
+/** 服务端 decrypt_image 会在目标路径后追加的扩展名 */
+const DECRYPTED_EXTENSIONS: [&str; 3] = ["png", "jpg", "gif"];
+
+/** 按消息类型确定的转发方式 */
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum Forward {
+    Text(String),
+    /** 图片 .dat 文件路径 */
+    Image(PathBuf),
+    File(PathBuf),
+    /** 表情下载地址 */
+    Emotion(String),
+    Xml(XmlMessage),
+}
+
+fn unsupported(reason: &str, msg: &wcf::WxMsg) -> WcfError {
+    warn!("{}: id {}, type {}", reason, msg.id, msg.r#type);
+    WcfError::InvalidArgument(format!("{}: type {}", reason, msg.r#type))
+}
+
+/** 路径字段为空时说明附件尚未下载 */
+fn attachment(path: &str, msg: &wcf::WxMsg) -> Result<PathBuf, WcfError> {
+    if path.is_empty() {
+        return Err(unsupported("附件尚未下载", msg));
+    }
+    Ok(PathBuf::from(path))
+}
+
+fn prepare(msg: &wcf::WxMsg) -> Result<Forward, WcfError> {
+    match msg.r#type {
+        MSG_TYPE_TEXT => Ok(Forward::Text(msg.content.clone())),
+        MSG_TYPE_IMAGE => Ok(Forward::Image(attachment(&msg.extra, msg)?)),
+        MSG_TYPE_EMOJI => match message::parse(msg) {
+            Message::Sticker(sticker) if !sticker.cdnurl.is_empty() => {
+                Ok(Forward::Emotion(sticker.cdnurl))
+            }
+            _ => Err(unsupported("无法获取表情地址", msg)),
+        },
+        MSG_TYPE_APP => {
+            let app = match message::parse(msg) {
+                Message::App(app) => app,
+                _ => return Err(unsupported("appmsg 解析失败", msg)),
+            };
+            let xml_type = match app {
+                AppMessage::File { .. } => return Ok(Forward::File(attachment(&msg.extra, msg)?)),
+                AppMessage::Transfer { .. } => return Err(unsupported("转账不能转发", msg)),
+                AppMessage::MiniProgram { .. } => XML_TYPE_MINI_PROGRAM,
+                _ => XML_TYPE_APP,
+            };
+            let xml = if msg.content.contains("<appmsg") {
+                &msg.content
+            } else {
+                &msg.xml
+            };
+            Ok(Forward::Xml(XmlMessage {
+                content: message::xml_body(xml).to_string(),
+                path: if msg.thumb.is_empty() {
+                    None
+                } else {
+                    Some(PathBuf::from(&msg.thumb))
+                },
+                xml_type,
+            }))
+        }
+        _ => Err(unsupported("不支持转发的消息类型", msg)),
+    }
+}
+
+/**
+ * 解密图片后发送
+ * 客户端能读到 .dat 文件时本地解密，否则交给服务端 decrypt_image 解密到 dl_path，
+ * 此时需要 path_mapper 中有覆盖 dl_path 的规则，客户端才能读到解密结果
+ */
+fn forward_image(
+    wechat: &mut WeChat,
+    src: &Path,
+    receiver: Receiver,
+    msg_id: u64,
+) -> Result<SendStatus, WcfError> {
+    if let Ok(image) = dat::decrypt_path(src) {
+        return media::send_image_from(wechat, Media::from(image.data), receiver);
+    }
+
+    let mapped = wechat
+        .path_mapper
+        .as_ref()
+        .is_some_and(|mapper| mapper.maps_local(&wechat.dl_path));
+    if !mapped {
+        error!(
+            "本地无法解密图片，且 path_mapper 未映射 dl_path，无法读取服务端解密结果: {}",
+            src.display()
+        );
+        return Err(WcfError::InvalidArgument(String::from(
+            "服务端解密需要 path_mapper 映射 dl_path",
+        )));
+    }
+
+    warn!("本地无法解密图片，改由服务端解密: {}", src.display());
+    let dst = wechat.dl_path.join(format!("forward-{}", msg_id));
+    let candidates: Vec<PathBuf> = DECRYPTED_EXTENSIONS
+        .iter()
+        .map(|ext| dst.with_extension(ext))
+        .collect();
+    let result = wechat::decrypt_image(
+        src.to_string_lossy().to_string(),
+        dst.to_string_lossy().to_string(),
+        wechat,
+    )
+    .and_then(|status| {
+        if !status.is_success() {
+            return Ok(status);
+        }
+        match candidates.iter().find(|path| path.exists()) {
+            Some(path) => wechat::send_image(wechat, path.clone(), receiver),
+            None => {
+                error!("找不到解密后的图片: {}", dst.display());
+                Err(WcfError::Other(String::from("找不到解密后的图片")))
+            }
+        }
+    });
+    // 无论发送成功与否都清理服务端写出的文件
+    for path in candidates.iter().filter(|path| path.exists()) {
+        if let Err(e) = fs::remove_file(path) {
+            warn!("清理解密图片失败: {}, {}", path.display(), e);
+        }
+    }
+    result
+}
+
+/**
+ * 将收到的消息按类型重新发送给其他接收人
+ * 文字、图片、文件、表情、appmsg 卡片（链接、小程序、音乐、聊天记录等）可转发；
+ * 文件和图片需已下载（extra 不为空），转账等消息不能转发
+ * @param msg: 收到的消息
+ * @param to:  转发目标
+ */
+pub fn forward(
+    wechat: &mut WeChat,
+    msg: &wcf::WxMsg,
+    to: impl Into<Receiver>,
+) -> Result<SendStatus, WcfError> {
+    let receiver = to.into();
+    match prepare(msg)? {
+        Forward::Text(text) => wechat::send_text(wechat, text, receiver, &[]),
+        Forward::Image(src) => forward_image(wechat, &src, receiver, msg.id),
+        Forward::File(path) => wechat::send_file(wechat, path, receiver),
+        Forward::Emotion(url) => media::send_emotion_from(wechat, Media::Url(url), receiver),
+        Forward::Xml(xml) => xml.send(wechat, receiver),
+    }
+}
+
+mod test {
+
+    #[test]
+    fn test_prepare() {
+        use std::path::PathBuf;
+
+        use crate::forward::{prepare, Forward};
+        use crate::wechat::wcf::WxMsg;
+        use crate::xmlmsg::{XML_TYPE_APP, XML_TYPE_MINI_PROGRAM};
+
+        let text = WxMsg {
+            r#type: 1,
+            content: String::from("hello"),
+            ..Default::default()
+        };
+        assert_eq!(
+            prepare(&text).unwrap(),
+            Forward::Text(String::from("hello"))
+        );
+
+        let image = WxMsg {
+            r#type: 3,
+            extra: String::from("C:\\WeChat Files\\wxid_me\\FileStorage\\Image\\abc.dat"),
+            ..Default::default()
+        };
+        assert_eq!(
+            prepare(&image).unwrap(),
+            Forward::Image(PathBuf::from(
+                "C:\\WeChat Files\\wxid_me\\FileStorage\\Image\\abc.dat"
+            ))
+        );
+        let pending = WxMsg {
+            extra: String::new(),
+            ..image
+        };
+        assert!(prepare(&pending).is_err());
+
+        let sticker = WxMsg {
+            r#type: 47,
+            content: String::from(
+                r#"<msg><emoji md5="3c5a" len="24512" cdnurl="http://wxapp.tc.qq.com/262/20304/stodownload?m=3c5a&amp;filekey=3035" width="240" height="180"></emoji></msg>"#,
+            ),
+            ..Default::default()
+        };
+        assert_eq!(
+            prepare(&sticker).unwrap(),
+            Forward::Emotion(String::from(
+                "http://wxapp.tc.qq.com/262/20304/stodownload?m=3c5a&filekey=3035"
+            ))
+        );
+
+        let file = WxMsg {
+            r#type: 49,
+            content: String::from(
+                "wxid_abc:\n<msg><appmsg><title>report.pdf</title><type>6</type></appmsg></msg>",
+            ),
+            extra: String::from("C:\\WeChat Files\\wxid_me\\FileStorage\\File\\report.pdf"),
+            ..Default::default()
+        };
+        assert_eq!(
+            prepare(&file).unwrap(),
+            Forward::File(PathBuf::from(
+                "C:\\WeChat Files\\wxid_me\\FileStorage\\File\\report.pdf"
+            ))
+        );
+
+        let link = WxMsg {
+            r#type: 49,
+            content: String::from(
+                "wxid_abc:\n<msg><appmsg><title>标题</title><type>5</type><url>https://example.com</url></appmsg></msg>",
+            ),
+            ..Default::default()
+        };
+        match prepare(&link).unwrap() {
+            Forward::Xml(xml) => {
+                assert!(xml.content.starts_with("<msg><appmsg>"));
+                assert_eq!(xml.xml_type, XML_TYPE_APP);
+                assert_eq!(xml.path, None);
+            }
+            other => panic!("unexpected: {:?}", other),
+        }
+
+        let mini_program = WxMsg {
+            r#type: 49,
+            content: String::from(
+                "<msg><appmsg><title>小程序</title><type>33</type><weappinfo><appid>wx123</appid></weappinfo></appmsg></msg>",
+            ),
+            thumb: String::from("C:\\thumb.jpg"),
+            ..Default::default()
+        };
+        match prepare(&mini_program).unwrap() {
+            Forward::Xml(xml) => {
+                assert_eq!(xml.xml_type, XML_TYPE_MINI_PROGRAM);
+                assert_eq!(xml.path, Some(PathBuf::from("C:\\thumb.jpg")));
+            }
+            other => panic!("unexpected: {:?}", other),
+        }
+
+        let transfer = WxMsg {
+            r#type: 49,
+            content: String::from(
+                "<msg><appmsg><title>转账</title><type>2000</type></appmsg></msg>",
+            ),
+            ..Default::default()
+        };
+        assert!(prepare(&transfer).is_err());
+
+        let voice = WxMsg {
+            r#type: 34,
+            ..Default::default()
+        };
+        assert!(prepare(&voice).is_err());
+    }
+
+    #[test]
+    fn test_forward() {
+        let mut wechat = crate::wechat::WeChat::default();
+        let msg = crate::wechat::wcf::WxMsg {
+            r#type: 1,
+            content: String::from("Hello, wcferry!"),
+            ..Default::default()
+        };
+        let status = crate::forward::forward(
+            &mut wechat,
+            &msg,
+            crate::receiver::SpecialAccount::FileHelper,
+        )
+        .unwrap();
+        println!("Success: {}", status);
+    }
+}
@@ -0,0 +1,267 @@
+use std::{
+    collections::HashMap,
+    sync::Mutex,
+    thread,
+    time::{Duration, Instant},
+};
+
+use log::warn;
+use rand::Rng;
+
+/** 按接收人分桶的表项超过该数量时清理已回满的桶 */
+const MAX_IDLE_BUCKETS: usize = 1024;
+
+/** 发送的消息类型，用于按类型限流 */
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum MsgKind {
+    Text,
+    Image,
+    File,
+    Xml,
+    Emotion,
+}
+
+/** 令牌桶速率：最多积攒 burst 个令牌，每 interval 补充一个 */
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Rate {
+    pub burst: u32,
+    pub interval: Duration,
+}
+
+impl Rate {
+    pub fn new(burst: u32, interval: Duration) -> Self {
+        Rate {
+            burst: burst.max(1),
+            interval,
+        }
+    }
+
+    /** 每秒 n 条，允许瞬时发送 n 条 */
+    pub fn per_second(n: u32) -> Self {
+        Rate::new(n, Duration::from_secs(1) / n.max(1))
+    }
+
+    /** 每分钟 n 条，允许瞬时发送 n 条 */
+    pub fn per_minute(n: u32) -> Self {
+        Rate::new(n, Duration::from_secs(60) / n.max(1))
+    }
+}
+
+/** 超过速率时的行为 */
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum LimitMode {
+    /** 阻塞等待直到可以发送 */
+    #[default]
+    Wait,
+    /** 直接返回错误 */
+    FailFast,
+}
+
+#[derive(Clone, Copy, Debug)]
+struct Bucket {
+    tokens: f64,
+    last: Instant,
+}
+
+impl Bucket {
+    fn full(rate: &Rate, now: Instant) -> Self {
+        Bucket {
+            tokens: rate.burst as f64,
+            last: now,
+        }
+    }
+
+    fn refill(&mut self, rate: &Rate, now: Instant) {
+        let elapsed = now.saturating_duration_since(self.last);
+        let added = if rate.interval.is_zero() {
+            rate.burst as f64
+        } else {
+            elapsed.as_secs_f64() / rate.interval.as_secs_f64()
+        };
+        self.tokens = (self.tokens + added).min(rate.burst as f64);
+        self.last = now;
+    }
+
+    /** 距离攒够一个令牌还需等待的时间 */
+    fn wait_time(&self, rate: &Rate) -> Duration {
+        if self.tokens >= 1.0 {
+            Duration::ZERO
+        } else {
+            rate.interval.mul_f64(1.0 - self.tokens)
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct State {
+    global: Option<Bucket>,
+    receivers: HashMap<String, Bucket>,
+    kinds: HashMap<MsgKind, Bucket>,
+}
+
+/**
+ * 发送限流，支持全局、按接收人、按消息类型三种令牌桶，以及随机延迟
+ * @example RateLimiter::new().global(Rate::per_minute(20)).per_receiver(Rate::per_minute(5))
+ */
+#[derive(Debug, Default)]
+pub struct RateLimiter {
+    global: Option<Rate>,
+    per_receiver: Option<Rate>,
+    per_kind: HashMap<MsgKind, Rate>,
+    jitter: Duration,
+    mode: LimitMode,
+    state: Mutex<State>,
+}
+
+impl RateLimiter {
+    pub fn new() -> Self {
+        RateLimiter::default()
+    }
+
+    pub fn global(mut self, rate: Rate) -> Self {
+        self.global = Some(rate);
+        self
+    }
+
+    pub fn per_receiver(mut self, rate: Rate) -> Self {
+        self.per_receiver = Some(rate);
+        self
+    }
+
+    pub fn per_kind(mut self, kind: MsgKind, rate: Rate) -> Self {
+        self.per_kind.insert(kind, rate);
+        self
+    }
+
+    /** 每次发送前额外随机等待 0 ~ jitter */
+    pub fn jitter(mut self, jitter: Duration) -> Self {
+        self.jitter = jitter;
+        self
+    }
+
+    pub fn mode(mut self, mode: LimitMode) -> Self {
+        self.mode = mode;
+        self
+    }
+
+    /**
+     * 获取一次发送许可
+     * @param receiver: 接收人
+     * @param kind:     消息类型
+     * @return FailFast 模式下超过速率时返回错误
+     */
+    pub fn acquire(&self, receiver: &str, kind: MsgKind) -> Result<(), Box<dyn std::error::Error>> {
+        loop {
+            let wait = self.try_acquire(receiver, kind, Instant::now());
+            if wait.is_zero() {
+                break;
+            }
+            if self.mode == LimitMode::FailFast {
+                warn!("发送过于频繁: {}, {:?}", receiver, kind);
+                return Err("发送过于频繁".into());
+            }
+            thread::sleep(wait);
+        }
+        if !self.jitter.is_zero() {
+            let jitter = rand::thread_rng().gen_range(Duration::ZERO..=self.jitter);
+            thread::sleep(jitter);
+        }
+        Ok(())
+    }
+
+    /** 所有桶都有令牌时扣减并返回 0，否则不扣减并返回需等待的时间 */
+    fn try_acquire(&self, receiver: &str, kind: MsgKind, now: Instant) -> Duration {
+        let mut state = self.state.lock().unwrap();
+        let State {
+            global,
+            receivers,
+            kinds,
+        } = &mut *state;
+
+        if receivers.len() > MAX_IDLE_BUCKETS {
+            if let Some(rate) = &self.per_receiver {
+                receivers.retain(|_, bucket| {
+                    bucket.refill(rate, now);
+                    bucket.tokens < rate.burst as f64
+                });
+            }
+        }
+
+        let mut buckets: Vec<(&mut Bucket, &Rate)> = vec![];
+        if let Some(rate) = &self.global {
+            buckets.push((global.get_or_insert_with(|| Bucket::full(rate, now)), rate));
+        }
+        if let Some(rate) = &self.per_receiver {
+            let bucket = receivers
+                .entry(receiver.to_string())
+                .or_insert_with(|| Bucket::full(rate, now));
+            buckets.push((bucket, rate));
+        }
+        if let Some(rate) = self.per_kind.get(&kind) {
+            let bucket = kinds.entry(kind).or_insert_with(|| Bucket::full(rate, now));
+            buckets.push((bucket, rate));
+        }
+
+        let mut wait = Duration::ZERO;
+        for (bucket, rate) in buckets.iter_mut() {
+            bucket.refill(rate, now);
+            wait = wait.max(bucket.wait_time(rate));
+        }
+        if wait.is_zero() {
+            for (bucket, _) in buckets.iter_mut() {
+                bucket.tokens -= 1.0;
+            }
+        }
+        wait
+    }
+}
+
+mod test {
+
+    #[test]
+    fn test_fail_fast() {
+        use std::time::Duration;
+
+        use crate::limiter::{LimitMode, MsgKind, Rate, RateLimiter};
+
+        let limiter = RateLimiter::new()
+            .global(Rate::new(3, Duration::from_secs(60)))
+            .per_receiver(Rate::new(2, Duration::from_secs(60)))
+            .mode(LimitMode::FailFast);
+        assert!(limiter.acquire("wxid_a", MsgKind::Text).is_ok());
+        assert!(limiter.acquire("wxid_a", MsgKind::Text).is_ok());
+        assert!(limiter.acquire("wxid_a", MsgKind::Text).is_err());
+        assert!(limiter.acquire("wxid_b", MsgKind::Image).is_ok());
+        assert!(limiter.acquire("wxid_c", MsgKind::Text).is_err());
+    }
+
+    #[test]
+    fn test_per_kind() {
+        use std::time::Duration;
+
+        use crate::limiter::{LimitMode, MsgKind, Rate, RateLimiter};
+
+        let limiter = RateLimiter::new()
+            .per_kind(MsgKind::Image, Rate::new(1, Duration::from_secs(60)))
+            .mode(LimitMode::FailFast);
+        assert!(limiter.acquire("wxid_a", MsgKind::Image).is_ok());
+        assert!(limiter.acquire("wxid_b", MsgKind::Image).is_err());
+        assert!(limiter.acquire("wxid_b", MsgKind::Text).is_ok());
+    }
+
+    #[test]
+    fn test_wait() {
+        use std::time::{Duration, Instant};
+
+        use crate::limiter::{MsgKind, Rate, RateLimiter};
+
+        let limiter = RateLimiter::new()
+            .global(Rate::new(1, Duration::from_millis(50)))
+            .jitter(Duration::from_millis(5));
+        let start = Instant::now();
+        for _ in 0..3 {
+            limiter.acquire("wxid_a", MsgKind::Text).unwrap();
+        }
+        assert!(start.elapsed() >= Duration::from_millis(100));
+    }
+}
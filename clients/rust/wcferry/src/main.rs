@@ -1,4 +1,28 @@
+mod broadcast;
+mod contact;
+mod contact_book;
+mod dat;
+mod echo;
+mod emoji;
+mod error;
+mod filter;
+mod forward;
+mod limiter;
+mod media;
+mod message;
+mod outbox;
+mod path_mapper;
+#[cfg(feature = "preprocess")]
+mod preprocess;
+mod receiver;
+mod reply;
+mod scheduler;
+mod silk;
+mod status;
+mod template;
+mod text;
 mod wechat;
+mod xmlmsg;
 
 fn main() {
     println!("Hello, wcferry!");
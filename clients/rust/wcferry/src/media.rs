@@ -0,0 +1,448 @@
+use std::{
+    borrow::Cow,
+    fs,
+    io::Read,
+    path::{Path, PathBuf},
+    sync::atomic::{AtomicU64, Ordering},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use log::{error, warn};
+
+use crate::{
+    dat,
+    error::WcfError,
+    receiver::Receiver,
+    status::SendStatus,
+    wechat::{self, WeChat},
+};
+
+const USER_AGENT: &str = "Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/100.0.4896.127 Safari/537.36";
+/** 下载超时 */
+const DOWNLOAD_TIMEOUT: Duration = Duration::from_secs(60);
+/** 下载大小上限 */
+const MAX_DOWNLOAD_SIZE: u64 = 100 * 1024 * 1024;
+/** dl_path 下存放暂存文件的子目录，只清理该目录中的内容 */
+const STAGING_DIR: &str = ".staging";
+/** 暂存目录中超过该时长的残留文件会被清理 */
+const STALE_AFTER: Duration = Duration::from_secs(600);
+
+static STAGE_COUNTER: AtomicU64 = AtomicU64::new(0);
+
+/** 待发送的图片/文件/表情来源 */
+pub enum Media<'a> {
+    /** 服务端可访问的路径，不做处理直接发送 */
+    Path(PathBuf),
+    Bytes(Cow<'a, [u8]>),
+    Reader(Box<dyn Read + 'a>),
+    /** http(s) 地址，先下载到暂存目录 */
+    Url(String),
+}
+
+impl<'a> Media<'a> {
+    pub fn reader<R: Read + 'a>(reader: R) -> Self {
+        Media::Reader(Box::new(reader))
+    }
+}
+
+impl<'a> From<&'a [u8]> for Media<'a> {
+    fn from(bytes: &'a [u8]) -> Self {
+        Media::Bytes(Cow::Borrowed(bytes))
+    }
+}
+
+impl From<Vec<u8>> for Media<'_> {
+    fn from(bytes: Vec<u8>) -> Self {
+        Media::Bytes(Cow::Owned(bytes))
+    }
+}
+
+impl From<PathBuf> for Media<'_> {
+    fn from(path: PathBuf) -> Self {
+        Media::Path(path)
+    }
+}
+
+/** 与 Python 客户端一致：http 开头视为网络地址，其余视为路径 */
+impl From<&str> for Media<'_> {
+    fn from(s: &str) -> Self {
+        if s.starts_with("http://") || s.starts_with("https://") {
+            Media::Url(s.to_string())
+        } else {
+            Media::Path(PathBuf::from(s))
+        }
+    }
+}
+
+/** 暂存到本地的文件，drop 时删除；交给微信发送时需 keep，由过期清理删除 */
+#[derive(Debug)]
+pub struct StagedFile {
+    path: PathBuf,
+    /** 暂存时创建的独立子目录，Path 来源时为 None */
+    dir: Option<PathBuf>,
+}
+
+impl StagedFile {
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /** 保留文件，drop 时不再删除，超过 STALE_AFTER 后由下一次暂存清理 */
+    pub fn keep(mut self) -> PathBuf {
+        self.dir = None;
+        std::mem::take(&mut self.path)
+    }
+}
+
+impl Drop for StagedFile {
+    fn drop(&mut self) {
+        if let Some(dir) = &self.dir {
+            if let Err(e) = fs::remove_dir_all(dir) {
+                warn!("清理暂存文件失败: {}, {}", dir.display(), e);
+            }
+        }
+    }
+}
+
+/**
+ * 将媒体内容暂存到 dl_path/.staging 下
+ * @param dl_path: 下载目录，需要服务端可访问
+ * @param media:   媒体来源
+ * @param name:    文件名（不含扩展名时按内容补全），为空时使用 URL 中的文件名
+ */
+pub fn stage(
+    dl_path: &Path,
+    media: Media,
+    name: Option<&str>,
+) -> Result<StagedFile, Box<dyn std::error::Error>> {
+    let (data, url_name, content_type) = match media {
+        Media::Path(path) => return Ok(StagedFile { path, dir: None }),
+        Media::Bytes(bytes) => (bytes.into_owned(), None, None),
+        Media::Reader(mut reader) => {
+            let mut data = Vec::new();
+            if let Err(e) = reader.read_to_end(&mut data) {
+                error!("读取媒体数据失败: {}", e);
+                return Err("媒体文件暂存失败".into());
+            }
+            (data, None, None)
+        }
+        Media::Url(url) => {
+            let (data, content_type) = download(&url)?;
+            (data, file_name_from_url(&url), content_type)
+        }
+    };
+
+    let mut file_name = sanitize(name.or(url_name.as_deref()).unwrap_or("file"));
+    if Path::new(&file_name).extension().is_none() {
+        let ext = sniff_extension(&data).or(content_type.as_deref().and_then(extension_for_mime));
+        if let Some(ext) = ext {
+            file_name = format!("{}.{}", file_name, ext);
+        }
+    }
+
+    let staging = dl_path.join(STAGING_DIR);
+    cleanup_stale(&staging);
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_nanos();
+    let dir = staging.join(format!(
+        "{}-{}",
+        nanos,
+        STAGE_COUNTER.fetch_add(1, Ordering::Relaxed)
+    ));
+    let path = dir.join(file_name);
+    let written = fs::create_dir_all(&dir).and_then(|_| fs::write(&path, &data));
+    if let Err(e) = written {
+        error!("写入暂存文件失败: {}, {}", path.display(), e);
+        let _ = fs::remove_dir_all(&dir);
+        return Err("媒体文件暂存失败".into());
+    }
+    Ok(StagedFile {
+        path,
+        dir: Some(dir),
+    })
+}
+
+fn download(url: &str) -> Result<(Vec<u8>, Option<String>), Box<dyn std::error::Error>> {
+    let response = match ureq::get(url)
+        .set("User-Agent", USER_AGENT)
+        .timeout(DOWNLOAD_TIMEOUT)
+        .call()
+    {
+        Ok(response) => response,
+        Err(e) => {
+            error!("网络资源下载失败: {}, {}", url, e);
+            return Err("网络资源下载失败".into());
+        }
+    };
+    let length = response
+        .header("Content-Length")
+        .and_then(|len| len.parse::<u64>().ok());
+    if length.is_some_and(|len| len > MAX_DOWNLOAD_SIZE) {
+        error!("网络资源过大: {}, {:?} 字节", url, length);
+        return Err("网络资源超过大小上限".into());
+    }
+    let content_type = response
+        .header("Content-Type")
+        .map(|_| response.content_type().to_string());
+    let mut data = Vec::new();
+    // 多读一个字节，用于判断是否超过上限
+    if let Err(e) = response
+        .into_reader()
+        .take(MAX_DOWNLOAD_SIZE + 1)
+        .read_to_end(&mut data)
+    {
+        error!("网络资源下载失败: {}, {}", url, e);
+        return Err("网络资源下载失败".into());
+    }
+    if data.len() as u64 > MAX_DOWNLOAD_SIZE {
+        error!("网络资源过大: {}, 超过 {} 字节", url, MAX_DOWNLOAD_SIZE);
+        return Err("网络资源超过大小上限".into());
+    }
+    Ok((data, content_type))
+}
+
+/** 是否为 stage 创建的子目录，名称为 <纳秒>-<序号> */
+fn is_stage_dir(name: &str) -> bool {
+    let digits = |s: &str| !s.is_empty() && s.bytes().all(|b| b.is_ascii_digit());
+    name.split_once('-')
+        .is_some_and(|(nanos, counter)| digits(nanos) && digits(counter))
+}
+
+/** 删除暂存目录中的残留文件（进程异常退出时未被清理的），只处理 stage 创建的子目录 */
+fn cleanup_stale(staging: &Path) {
+    let entries = match fs::read_dir(staging) {
+        Ok(entries) => entries,
+        Err(_) => return,
+    };
+    for entry in entries.flatten() {
+        let named = entry.file_name().to_str().is_some_and(is_stage_dir);
+        let is_dir = entry.file_type().is_ok_and(|t| t.is_dir());
+        if !named || !is_dir {
+            continue;
+        }
+        let stale = entry
+            .metadata()
+            .and_then(|meta| meta.modified())
+            .ok()
+            .and_then(|modified| modified.elapsed().ok())
+            .is_some_and(|age| age > STALE_AFTER);
+        if stale {
+            let _ = fs::remove_dir_all(entry.path());
+        }
+    }
+}
+
+/** 取 URL 路径的最后一段作为文件名 */
+fn file_name_from_url(url: &str) -> Option<String> {
+    let path = url.split(['?', '#']).next().unwrap_or(url);
+    let path = path.split_once("://").map_or(path, |(_, rest)| rest);
+    let (_, path) = path.split_once('/')?;
+    let name = path.rsplit('/').next()?;
+    if name.is_empty() {
+        None
+    } else {
+        Some(name.to_string())
+    }
+}
+
+/** 去掉文件名中 Windows 不允许的字符 */
+fn sanitize(name: &str) -> String {
+    let name: String = name
+        .chars()
+        .map(|c| match c {
+            '<' | '>' | ':' | '"' | '/' | '\\' | '|' | '?' | '*' => '_',
+            c if c.is_control() => '_',
+            c => c,
+        })
+        .collect();
+    let name = name.trim_matches([' ', '.']);
+    if name.is_empty() {
+        String::from("file")
+    } else {
+        name.to_string()
+    }
+}
+
+/** 按魔数识别扩展名 */
+pub fn sniff_extension(data: &[u8]) -> Option<&'static str> {
+    if let Some((format, 0)) = dat::detect(data) {
+        return Some(format.extension());
+    }
+    if data.starts_with(b"%PDF") {
+        Some("pdf")
+    } else if data.starts_with(b"PK\x03\x04") {
+        Some("zip")
+    } else if data.len() >= 12 && &data[4..8] == b"ftyp" {
+        Some("mp4")
+    } else if data.starts_with(b"ID3") {
+        Some("mp3")
+    } else if data.len() >= 12 && data.starts_with(b"RIFF") && &data[8..12] == b"WAVE" {
+        Some("wav")
+    } else {
+        None
+    }
+}
+
+/** 按 Content-Type 推断扩展名 */
+pub fn extension_for_mime(mime: &str) -> Option<&'static str> {
+    let ext = match mime {
+        "image/jpeg" | "image/jpg" => "jpg",
+        "image/png" => "png",
+        "image/gif" => "gif",
+        "image/webp" => "webp",
+        "image/bmp" => "bmp",
+        "application/pdf" => "pdf",
+        "application/zip" => "zip",
+        "video/mp4" => "mp4",
+        "audio/mpeg" => "mp3",
+        "audio/wav" | "audio/x-wav" => "wav",
+        "text/plain" => "txt",
+        _ => return None,
+    };
+    Some(ext)
+}
+
+/**
+ * 暂存待发送的媒体并保留文件
+ * 微信在发送接口返回后才异步上传，文件不能随即删除，留给 cleanup_stale 按时长清理
+ */
+pub(crate) fn stage_for_send(
+    wechat: &WeChat,
+    media: Media,
+    name: Option<&str>,
+) -> Result<PathBuf, WcfError> {
+    if let Media::Path(path) = media {
+        return Ok(path);
+    }
+    let mapped = match &wechat.path_mapper {
+        Some(mapper) => mapper.maps_local(&wechat.dl_path),
+        None => true,
+    };
+    if !mapped {
+        error!(
+            "path_mapper 未映射 dl_path，服务端无法读取暂存文件: {}",
+            wechat.dl_path.display()
+        );
+        return Err(WcfError::InvalidArgument(String::from(
+            "暂存发送需要 path_mapper 映射 dl_path",
+        )));
+    }
+    Ok(stage(&wechat.dl_path, media, name)?.keep())
+}
+
+/** 发送图片，支持路径、字节、reader 和 URL */
+pub fn send_image_from<'a>(
+    wechat: &mut WeChat,
+    media: impl Into<Media<'a>>,
+    receiver: impl Into<Receiver>,
+) -> Result<SendStatus, WcfError> {
+    let path = stage_for_send(wechat, media.into(), None)?;
+    wechat::send_image(wechat, path, receiver)
+}
+
+/**
+ * 发送文件，支持路径、字节、reader 和 URL
+ * @param name: 接收方看到的文件名，为空时使用 URL 中的文件名
+ */
+pub fn send_file_from<'a>(
+    wechat: &mut WeChat,
+    media: impl Into<Media<'a>>,
+    name: Option<&str>,
+    receiver: impl Into<Receiver>,
+) -> Result<SendStatus, WcfError> {
+    let path = stage_for_send(wechat, media.into(), name)?;
+    wechat::send_file(wechat, path, receiver)
+}
+
+/** 发送表情，支持路径、字节、reader 和 URL */
+pub fn send_emotion_from<'a>(
+    wechat: &mut WeChat,
+    media: impl Into<Media<'a>>,
+    receiver: impl Into<Receiver>,
+) -> Result<SendStatus, WcfError> {
+    let path = stage_for_send(wechat, media.into(), None)?;
+    wechat::send_emotion(wechat, path, receiver)
+}
+
+mod test {
+
+    #[test]
+    fn test_stage_bytes() {
+        use crate::media::{stage, Media};
+
+        let dl_path = std::env::temp_dir().join("wcferry_test_media");
+        let png = b"\x89PNG\r\n\x1a\n\x00\x00\x00\rIHDR".to_vec();
+        let staged = stage(&dl_path, Media::from(png.clone()), None).unwrap();
+        assert_eq!(staged.path().file_name().unwrap(), "file.png");
+        assert!(staged.path().starts_with(dl_path.join(".staging")));
+        assert_eq!(std::fs::read(staged.path()).unwrap(), png);
+
+        let path = staged.path().to_path_buf();
+        drop(staged);
+        assert!(!path.exists());
+
+        let staged = stage(&dl_path, Media::from(png.clone()), None).unwrap();
+        let path = staged.keep();
+        assert!(path.exists());
+        std::fs::remove_dir_all(path.parent().unwrap()).unwrap();
+
+        let staged = stage(&dl_path, Media::reader(&b"%PDF-1.4"[..]), Some("报告")).unwrap();
+        assert_eq!(staged.path().file_name().unwrap(), "报告.pdf");
+        let staged = stage(&dl_path, Media::from(&b"hello"[..]), Some("a/b:c.txt")).unwrap();
+        assert_eq!(staged.path().file_name().unwrap(), "a_b_c.txt");
+    }
+
+    #[test]
+    fn test_is_stage_dir() {
+        use crate::media::is_stage_dir;
+
+        assert!(is_stage_dir("1700000000000000000-0"));
+        assert!(is_stage_dir("1-23"));
+        assert!(!is_stage_dir("forward-123"));
+        assert!(!is_stage_dir("1700000000000000000"));
+        assert!(!is_stage_dir("1-"));
+        assert!(!is_stage_dir("photos"));
+    }
+
+    #[test]
+    fn test_media_from_str() {
+        use crate::media::Media;
+
+        assert!(matches!(
+            Media::from("https://example.com/a.jpg"),
+            Media::Url(_)
+        ));
+        assert!(matches!(
+            Media::from("C:\\Users\\Administrator\\Pictures\\1.jpg"),
+            Media::Path(_)
+        ));
+    }
+
+    #[test]
+    fn test_file_name_from_url() {
+        use crate::media::file_name_from_url;
+
+        assert_eq!(
+            file_name_from_url("https://example.com/img/a.jpg?x=1#top"),
+            Some(String::from("a.jpg"))
+        );
+        assert_eq!(file_name_from_url("https://example.com/"), None);
+        assert_eq!(file_name_from_url("https://example.com"), None);
+        assert_eq!(crate::media::extension_for_mime("image/jpeg"), Some("jpg"));
+        assert_eq!(crate::media::sniff_extension(b"GIF89a"), Some("gif"));
+    }
+
+    #[test]
+    fn test_send_image_from_url() {
+        let mut wechat = crate::wechat::WeChat::default();
+        let status = crate::media::send_image_from(
+            &mut wechat,
+            "https://raw.githubusercontent.com/lich0821/WeChatFerry/master/assets/QR.jpeg",
+            crate::receiver::SpecialAccount::FileHelper,
+        )
+        .unwrap();
+        println!("Success: {}", status);
+    }
+}
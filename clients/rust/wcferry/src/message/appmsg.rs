@@ -0,0 +1,402 @@
+use log::warn;
+
+use super::{find, number, text, xml_body};
+
+pub const APP_TYPE_MUSIC: u32 = 3;
+pub const APP_TYPE_LINK: u32 = 5;
+pub const APP_TYPE_FILE: u32 = 6;
+pub const APP_TYPE_CHAT_RECORD: u32 = 19;
+pub const APP_TYPE_MINI_PROGRAM: u32 = 33;
+pub const APP_TYPE_MINI_PROGRAM_SHARE: u32 = 36;
+pub const APP_TYPE_QUOTE: u32 = 57;
+pub const APP_TYPE_TRANSFER: u32 = 2000;
+
+/** 被引用的消息 */
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct QuotedMessage {
+    /** 原消息 id（svrid） */
+    pub id: u64,
+    /** 原消息类型 */
+    pub msg_type: u32,
+    /** 原消息发送者 wxid */
+    pub sender: String,
+    /** 原消息发送者显示名 */
+    pub display_name: String,
+    pub content: String,
+}
+
+/** 合并转发的聊天记录中的一条 */
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct RecordItem {
+    pub data_type: u32,
+    pub source_name: String,
+    pub source_time: String,
+    /** 文本内容或文件标题 */
+    pub content: String,
+}
+
+/** appmsg（type 49）按 `<appmsg><type>` 区分的子类型 */
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum AppMessage {
+    File {
+        name: String,
+        size: u64,
+        ext: String,
+        md5: String,
+        attach_id: String,
+    },
+    Link {
+        title: String,
+        desc: String,
+        url: String,
+        thumb_url: String,
+        source: String,
+    },
+    MiniProgram {
+        /** 33 或 36（分享的小程序） */
+        app_type: u32,
+        title: String,
+        appid: String,
+        username: String,
+        pagepath: String,
+        icon_url: String,
+        source: String,
+    },
+    Music {
+        title: String,
+        desc: String,
+        url: String,
+        data_url: String,
+    },
+    ChatRecord {
+        title: String,
+        desc: String,
+        items: Vec<RecordItem>,
+    },
+    Quote {
+        /** 回复内容 */
+        title: String,
+        refer: QuotedMessage,
+    },
+    Transfer {
+        title: String,
+        /** 金额描述，如 ￥0.10 */
+        fee_desc: String,
+        pay_subtype: u32,
+        transfer_id: String,
+        transaction_id: String,
+        memo: String,
+    },
+    /** 未单独解析的子类型 */
+    Other {
+        app_type: u32,
+        title: String,
+        desc: String,
+        url: String,
+    },
+}
+
+impl AppMessage {
+    /**
+     * 解析 appmsg XML
+     * @param xml: WxMsg.content（群消息可带 `wxid:\n` 前缀）
+     */
+    pub fn parse(xml: &str) -> Result<AppMessage, Box<dyn std::error::Error>> {
+        let doc = match roxmltree::Document::parse(xml_body(xml)) {
+            Ok(doc) => doc,
+            Err(e) => {
+                warn!("appmsg 解析失败: {}", e);
+                return Err("appmsg 解析失败".into());
+            }
+        };
+        let root = doc.root_element();
+        let appmsg = if root.has_tag_name("appmsg") {
+            root
+        } else {
+            match find(root, &["appmsg"]) {
+                Some(node) => node,
+                None => {
+                    warn!("appmsg 节点不存在");
+                    return Err("appmsg 解析失败".into());
+                }
+            }
+        };
+
+        let app_type: u32 = number(appmsg, &["type"]);
+        let title = text(appmsg, &["title"]);
+        let desc = text(appmsg, &["des"]);
+        let url = text(appmsg, &["url"]);
+        let msg = match app_type {
+            APP_TYPE_FILE => AppMessage::File {
+                name: title,
+                size: number(appmsg, &["appattach", "totallen"]),
+                ext: text(appmsg, &["appattach", "fileext"]),
+                md5: text(appmsg, &["md5"]),
+                attach_id: text(appmsg, &["appattach", "attachid"]),
+            },
+            APP_TYPE_LINK => AppMessage::Link {
+                title,
+                desc,
+                url,
+                thumb_url: text(appmsg, &["thumburl"]),
+                source: text(appmsg, &["sourcedisplayname"]),
+            },
+            APP_TYPE_MINI_PROGRAM | APP_TYPE_MINI_PROGRAM_SHARE => AppMessage::MiniProgram {
+                app_type,
+                title,
+                appid: text(appmsg, &["weappinfo", "appid"]),
+                username: text(appmsg, &["weappinfo", "username"]),
+                pagepath: text(appmsg, &["weappinfo", "pagepath"]),
+                icon_url: text(appmsg, &["weappinfo", "weappiconurl"]),
+                source: text(appmsg, &["sourcedisplayname"]),
+            },
+            APP_TYPE_MUSIC => AppMessage::Music {
+                title,
+                desc,
+                url,
+                data_url: text(appmsg, &["dataurl"]),
+            },
+            APP_TYPE_CHAT_RECORD => AppMessage::ChatRecord {
+                title,
+                desc,
+                items: parse_record_items(&text(appmsg, &["recorditem"])),
+            },
+            APP_TYPE_QUOTE => {
+                let refer = match find(appmsg, &["refermsg"]) {
+                    Some(refer) => {
+                        let chat_user = text(refer, &["chatusr"]);
+                        QuotedMessage {
+                            id: number(refer, &["svrid"]),
+                            msg_type: number(refer, &["type"]),
+                            sender: if chat_user.is_empty() {
+                                text(refer, &["fromusr"])
+                            } else {
+                                chat_user
+                            },
+                            display_name: text(refer, &["displayname"]),
+                            content: text(refer, &["content"]),
+                        }
+                    }
+                    None => QuotedMessage::default(),
+                };
+                AppMessage::Quote { title, refer }
+            }
+            APP_TYPE_TRANSFER => AppMessage::Transfer {
+                title,
+                fee_desc: text(appmsg, &["wcpayinfo", "feedesc"]),
+                pay_subtype: number(appmsg, &["wcpayinfo", "paysubtype"]),
+                transfer_id: text(appmsg, &["wcpayinfo", "transferid"]),
+                transaction_id: text(appmsg, &["wcpayinfo", "transcationid"]),
+                memo: text(appmsg, &["wcpayinfo", "pay_memo"]),
+            },
+            _ => AppMessage::Other {
+                app_type,
+                title,
+                desc,
+                url,
+            },
+        };
+        Ok(msg)
+    }
+
+    /** `<appmsg><type>` 的值 */
+    pub fn app_type(&self) -> u32 {
+        match self {
+            AppMessage::File { .. } => APP_TYPE_FILE,
+            AppMessage::Link { .. } => APP_TYPE_LINK,
+            AppMessage::MiniProgram { app_type, .. } => *app_type,
+            AppMessage::Music { .. } => APP_TYPE_MUSIC,
+            AppMessage::ChatRecord { .. } => APP_TYPE_CHAT_RECORD,
+            AppMessage::Quote { .. } => APP_TYPE_QUOTE,
+            AppMessage::Transfer { .. } => APP_TYPE_TRANSFER,
+            AppMessage::Other { app_type, .. } => *app_type,
+        }
+    }
+}
+
+/** recorditem 内是转义后的 `<recordinfo>` XML，需要再解析一次 */
+fn parse_record_items(xml: &str) -> Vec<RecordItem> {
+    if xml.is_empty() {
+        return vec![];
+    }
+    let doc = match roxmltree::Document::parse(xml_body(xml)) {
+        Ok(doc) => doc,
+        Err(e) => {
+            warn!("聊天记录解析失败: {}", e);
+            return vec![];
+        }
+    };
+    let datalist = match find(doc.root_element(), &["datalist"]) {
+        Some(node) => node,
+        None => return vec![],
+    };
+    datalist
+        .children()
+        .filter(|n| n.has_tag_name("dataitem"))
+        .map(|item| {
+            let desc = text(item, &["datadesc"]);
+            RecordItem {
+                data_type: item
+                    .attribute("datatype")
+                    .and_then(|t| t.parse().ok())
+                    .unwrap_or_default(),
+                source_name: text(item, &["sourcename"]),
+                source_time: text(item, &["sourcetime"]),
+                content: if desc.is_empty() {
+                    text(item, &["datatitle"])
+                } else {
+                    desc
+                },
+            }
+        })
+        .collect()
+}
+
+mod test {
+
+    #[test]
+    fn test_parse_file() {
+        use crate::message::AppMessage;
+
+        let xml = r#"wxid_abc:
+<?xml version="1.0"?>
+<msg>
+    <appmsg appid="" sdkver="0">
+        <title>report.pdf</title>
+        <des />
+        <type>6</type>
+        <appattach>
+            <totallen>102400</totallen>
+            <attachid>@cdn_abc</attachid>
+            <fileext>pdf</fileext>
+        </appattach>
+        <md5>d41d8cd98f00b204e9800998ecf8427e</md5>
+    </appmsg>
+</msg>"#;
+        assert_eq!(
+            AppMessage::parse(xml).unwrap(),
+            AppMessage::File {
+                name: String::from("report.pdf"),
+                size: 102400,
+                ext: String::from("pdf"),
+                md5: String::from("d41d8cd98f00b204e9800998ecf8427e"),
+                attach_id: String::from("@cdn_abc"),
+            }
+        );
+    }
+
+    #[test]
+    fn test_parse_link_and_mini_program() {
+        use crate::message::AppMessage;
+
+        let xml = r#"<msg><appmsg><title>标题</title><des>描述 &amp; 摘要</des><type>5</type>
+            <url><![CDATA[https://mp.weixin.qq.com/s?a=1&b=2]]></url>
+            <thumburl>https://example.com/t.jpg</thumburl>
+            <sourcedisplayname>公众号</sourcedisplayname></appmsg></msg>"#;
+        assert_eq!(
+            AppMessage::parse(xml).unwrap(),
+            AppMessage::Link {
+                title: String::from("标题"),
+                desc: String::from("描述 & 摘要"),
+                url: String::from("https://mp.weixin.qq.com/s?a=1&b=2"),
+                thumb_url: String::from("https://example.com/t.jpg"),
+                source: String::from("公众号"),
+            }
+        );
+
+        let xml = r#"<msg><appmsg><title>小程序</title><type>33</type>
+            <sourcedisplayname>某小程序</sourcedisplayname>
+            <weappinfo><username>gh_123@app</username><appid>wx1234567890</appid>
+            <pagepath><![CDATA[pages/index.html?id=1]]></pagepath></weappinfo></appmsg></msg>"#;
+        match AppMessage::parse(xml).unwrap() {
+            AppMessage::MiniProgram {
+                appid,
+                pagepath,
+                username,
+                ..
+            } => {
+                assert_eq!(appid, "wx1234567890");
+                assert_eq!(pagepath, "pages/index.html?id=1");
+                assert_eq!(username, "gh_123@app");
+            }
+            other => panic!("unexpected: {:?}", other),
+        }
+        let shared = xml.replace("<type>33</type>", "<type>36</type>");
+        assert_eq!(AppMessage::parse(&shared).unwrap().app_type(), 36);
+        assert_eq!(AppMessage::parse(xml).unwrap().app_type(), 33);
+    }
+
+    #[test]
+    fn test_parse_quote() {
+        use crate::message::appmsg::{AppMessage, QuotedMessage};
+
+        let xml = r#"<msg><appmsg appid="" sdkver="0"><title>收到</title><des></des><type>57</type>
+            <refermsg><type>1</type><svrid>8541264785412365478</svrid>
+            <fromusr>34476879773@chatroom</fromusr><chatusr>wxid_abc</chatusr>
+            <displayname>张三</displayname><content>明天开会</content></refermsg>
+            </appmsg><fromusername>wxid_me</fromusername></msg>"#;
+        assert_eq!(
+            AppMessage::parse(xml).unwrap(),
+            AppMessage::Quote {
+                title: String::from("收到"),
+                refer: QuotedMessage {
+                    id: 8541264785412365478,
+                    msg_type: 1,
+                    sender: String::from("wxid_abc"),
+                    display_name: String::from("张三"),
+                    content: String::from("明天开会"),
+                },
+            }
+        );
+    }
+
+    #[test]
+    fn test_parse_chat_record() {
+        use crate::message::AppMessage;
+
+        let xml = r#"<msg><appmsg><title>群聊的聊天记录</title><des>张三: 你好</des><type>19</type>
+            <recorditem><![CDATA[<recordinfo><title>群聊的聊天记录</title><datalist count="2">
+            <dataitem datatype="1" dataid="1"><datadesc>你好</datadesc><sourcename>张三</sourcename><sourcetime>2023-07-01 10:00</sourcetime></dataitem>
+            <dataitem datatype="8" dataid="2"><datatitle>a.txt</datatitle><sourcename>李四</sourcename><sourcetime>2023-07-01 10:01</sourcetime></dataitem>
+            </datalist></recordinfo>]]></recorditem></appmsg></msg>"#;
+        match AppMessage::parse(xml).unwrap() {
+            AppMessage::ChatRecord { title, items, .. } => {
+                assert_eq!(title, "群聊的聊天记录");
+                assert_eq!(items.len(), 2);
+                assert_eq!(items[0].source_name, "张三");
+                assert_eq!(items[0].content, "你好");
+                assert_eq!(items[1].data_type, 8);
+                assert_eq!(items[1].content, "a.txt");
+            }
+            other => panic!("unexpected: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_parse_transfer_and_other() {
+        use crate::message::AppMessage;
+
+        let xml = r#"<msg><appmsg><title>微信转账</title><type>2000</type><wcpayinfo>
+            <paysubtype>1</paysubtype><feedesc>￥0.10</feedesc>
+            <transcationid>100005000123063000081247810011296088</transcationid>
+            <transferid>1000050001202306300415889890620</transferid><pay_memo></pay_memo>
+            </wcpayinfo></appmsg></msg>"#;
+        match AppMessage::parse(xml).unwrap() {
+            AppMessage::Transfer {
+                fee_desc,
+                transfer_id,
+                pay_subtype,
+                ..
+            } => {
+                assert_eq!(fee_desc, "￥0.10");
+                assert_eq!(transfer_id, "1000050001202306300415889890620");
+                assert_eq!(pay_subtype, 1);
+            }
+            other => panic!("unexpected: {:?}", other),
+        }
+
+        let xml = "<msg><appmsg><title>视频号</title><type>51</type></appmsg></msg>";
+        assert_eq!(AppMessage::parse(xml).unwrap().app_type(), 51);
+        assert!(AppMessage::parse("not xml").is_err());
+    }
+}
@@ -0,0 +1,82 @@
+use log::warn;
+
+use super::xml_body;
+
+/** 名片消息（type 42） */
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ContactCard {
+    /** 好友为 wxid，非好友为 v3_xxx@stranger，可作为 accept_new_friend 的 v3 */
+    pub username: String,
+    pub nickname: String,
+    /** 微信号 */
+    pub alias: String,
+    /** 可作为 accept_new_friend 的 v4 */
+    pub v4: String,
+    pub province: String,
+    pub city: String,
+    /** 性别：1 男，2 女，0 未知 */
+    pub sex: i32,
+}
+
+impl ContactCard {
+    /**
+     * 解析名片消息 XML
+     * @param xml: WxMsg.content（群消息可带 `wxid:\n` 前缀）
+     */
+    pub fn parse(xml: &str) -> Result<ContactCard, Box<dyn std::error::Error>> {
+        let doc = match roxmltree::Document::parse(xml_body(xml)) {
+            Ok(doc) => doc,
+            Err(e) => {
+                warn!("名片消息解析失败: {}", e);
+                return Err("名片消息解析失败".into());
+            }
+        };
+        let msg = doc.root_element();
+        let attr = |name: &str| msg.attribute(name).unwrap_or_default().to_string();
+        let username = attr("username");
+        if username.is_empty() {
+            warn!("名片消息缺少 username");
+            return Err("名片消息解析失败".into());
+        }
+        Ok(ContactCard {
+            username,
+            nickname: attr("nickname"),
+            alias: attr("alias"),
+            v4: attr("antispamticket"),
+            province: attr("province"),
+            city: attr("city"),
+            sex: attr("sex").parse().unwrap_or_default(),
+        })
+    }
+
+    /** accept_new_friend 所需的 v3 */
+    pub fn v3(&self) -> &str {
+        &self.username
+    }
+}
+
+mod test {
+
+    #[test]
+    fn test_parse_contact_card() {
+        use crate::message::card::ContactCard;
+
+        let xml = r#"<?xml version="1.0"?>
+<msg bigheadimgurl="" smallheadimgurl="" username="v3_020b3826fd03010000000000d65613e9435fd2@stranger" nickname="张三" fullpy="zhangsan" shortpy="" alias="zhangsan_88" imagestatus="3" scene="17" province="广东" city="深圳" sign="" sex="1" certflag="0" certinfo="" brandIconUrl="" brandHomeUrl="" brandSubscriptConfigUrl="" brandFlags="0" regionCode="CN_Guangdong_Shenzhen" antispamticket="v4_000b708f0b0400000100000000003c3767b326@stranger" />"#;
+        let card = ContactCard::parse(xml).unwrap();
+        assert_eq!(
+            card.v3(),
+            "v3_020b3826fd03010000000000d65613e9435fd2@stranger"
+        );
+        assert_eq!(
+            card.v4,
+            "v4_000b708f0b0400000100000000003c3767b326@stranger"
+        );
+        assert_eq!(card.nickname, "张三");
+        assert_eq!(card.alias, "zhangsan_88");
+        assert_eq!(card.province, "广东");
+        assert_eq!(card.city, "深圳");
+        assert_eq!(card.sex, 1);
+        assert!(ContactCard::parse("<msg />").is_err());
+    }
+}
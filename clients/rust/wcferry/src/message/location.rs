@@ -0,0 +1,73 @@
+use log::warn;
+
+use super::{find, xml_body};
+
+/** 位置消息（type 48） */
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Location {
+    /** 纬度 */
+    pub lat: f64,
+    /** 经度 */
+    pub lon: f64,
+    /** 地图缩放级别 */
+    pub scale: u32,
+    /** 详细地址 */
+    pub label: String,
+    /** 地点名称 */
+    pub poiname: String,
+}
+
+impl Location {
+    /**
+     * 解析位置消息 XML
+     * @param xml: WxMsg.content（群消息可带 `wxid:\n` 前缀）
+     */
+    pub fn parse(xml: &str) -> Result<Location, Box<dyn std::error::Error>> {
+        let doc = match roxmltree::Document::parse(xml_body(xml)) {
+            Ok(doc) => doc,
+            Err(e) => {
+                warn!("位置消息解析失败: {}", e);
+                return Err("位置消息解析失败".into());
+            }
+        };
+        let location = match find(doc.root_element(), &["location"]) {
+            Some(node) => node,
+            None => {
+                warn!("location 节点不存在");
+                return Err("位置消息解析失败".into());
+            }
+        };
+        let attr = |name: &str| location.attribute(name).unwrap_or_default();
+        Ok(Location {
+            lat: attr("x").parse().unwrap_or_default(),
+            lon: attr("y").parse().unwrap_or_default(),
+            scale: attr("scale").parse().unwrap_or_default(),
+            label: attr("label").to_string(),
+            poiname: attr("poiname").to_string(),
+        })
+    }
+}
+
+mod test {
+
+    #[test]
+    fn test_parse_location() {
+        use crate::message::location::Location;
+
+        let xml = r#"<?xml version="1.0"?>
+<msg>
+	<location x="39.904989" y="116.405285" scale="15" label="北京市东城区东长安街" maptype="roadmap" poiname="天安门" poiid="" />
+</msg>"#;
+        assert_eq!(
+            Location::parse(xml).unwrap(),
+            Location {
+                lat: 39.904989,
+                lon: 116.405285,
+                scale: 15,
+                label: String::from("北京市东城区东长安街"),
+                poiname: String::from("天安门"),
+            }
+        );
+        assert!(Location::parse("<msg></msg>").is_err());
+    }
+}
@@ -0,0 +1,133 @@
+pub mod appmsg;
+pub mod card;
+pub mod location;
+pub mod sticker;
+pub mod system;
+
+pub use appmsg::AppMessage;
+pub use card::ContactCard;
+pub use location::Location;
+pub use sticker::Sticker;
+pub use system::SysEvent;
+
+use crate::{emoji, wechat::wcf};
+
+pub const MSG_TYPE_TEXT: u32 = 1;
+pub const MSG_TYPE_IMAGE: u32 = 3;
+pub const MSG_TYPE_CARD: u32 = 42;
+pub const MSG_TYPE_EMOJI: u32 = 47;
+pub const MSG_TYPE_LOCATION: u32 = 48;
+pub const MSG_TYPE_APP: u32 = 49;
+pub const MSG_TYPE_SYS: u32 = 10000;
+pub const MSG_TYPE_SYS_XML: u32 = 10002;
+
+/** 按消息类型解析后的消息内容 */
+#[derive(Clone, Debug, PartialEq)]
+pub enum Message {
+    Text(String),
+    ContactCard(ContactCard),
+    Sticker(Sticker),
+    Location(Location),
+    App(AppMessage),
+    System(SysEvent),
+    /** 暂不解析或解析失败的消息，保留原始类型 */
+    Unknown(u32),
+}
+
+/** 解析选项 */
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct ParseOptions {
+    /** 将文字及引用回复中的 [微笑] 等表情代码转为 Unicode emoji */
+    pub emoji: bool,
+}
+
+/** 将 WxMsg 解析为 Message */
+pub fn parse(msg: &wcf::WxMsg) -> Message {
+    parse_with(msg, &ParseOptions::default())
+}
+
+/** 按选项将 WxMsg 解析为 Message */
+pub fn parse_with(msg: &wcf::WxMsg, options: &ParseOptions) -> Message {
+    let message = parse_raw(msg);
+    if !options.emoji {
+        return message;
+    }
+    match message {
+        Message::Text(text) => Message::Text(emoji::to_unicode(&text).into_owned()),
+        Message::App(AppMessage::Quote { title, mut refer }) => {
+            if refer.msg_type == MSG_TYPE_TEXT {
+                refer.content = emoji::to_unicode(&refer.content).into_owned();
+            }
+            Message::App(AppMessage::Quote {
+                title: emoji::to_unicode(&title).into_owned(),
+                refer,
+            })
+        }
+        message => message,
+    }
+}
+
+fn parse_raw(msg: &wcf::WxMsg) -> Message {
+    match msg.r#type {
+        MSG_TYPE_TEXT => Message::Text(msg.content.clone()),
+        MSG_TYPE_CARD => match ContactCard::parse(&msg.content) {
+            Ok(card) => Message::ContactCard(card),
+            Err(_) => Message::Unknown(msg.r#type),
+        },
+        MSG_TYPE_EMOJI => match Sticker::parse(&msg.content) {
+            Ok(sticker) => Message::Sticker(sticker),
+            Err(_) => Message::Unknown(msg.r#type),
+        },
+        MSG_TYPE_LOCATION => match Location::parse(&msg.content) {
+            Ok(location) => Message::Location(location),
+            Err(_) => Message::Unknown(msg.r#type),
+        },
+        MSG_TYPE_APP => {
+            let xml = if msg.content.contains("<appmsg") {
+                &msg.content
+            } else {
+                &msg.xml
+            };
+            match AppMessage::parse(xml) {
+                Ok(app) => Message::App(app),
+                Err(_) => Message::Unknown(msg.r#type),
+            }
+        }
+        MSG_TYPE_SYS | MSG_TYPE_SYS_XML => Message::System(SysEvent::parse(&msg.content)),
+        t => Message::Unknown(t),
+    }
+}
+
+/** 群消息的 content 可能带有 `wxid_xxx:\n` 前缀，截取从第一个 `<` 开始的 XML */
+pub(crate) fn xml_body(content: &str) -> &str {
+    match content.find('<') {
+        Some(start) => content[start..].trim_end(),
+        None => content,
+    }
+}
+
+/** 按标签路径查找子节点 */
+fn find<'a, 'input>(
+    node: roxmltree::Node<'a, 'input>,
+    path: &[&str],
+) -> Option<roxmltree::Node<'a, 'input>> {
+    let mut node = node;
+    for name in path {
+        node = node.children().find(|n| n.has_tag_name(*name))?;
+    }
+    Some(node)
+}
+
+/** 按标签路径取文本，不存在时返回空字符串 */
+fn text(node: roxmltree::Node, path: &[&str]) -> String {
+    find(node, path)
+        .and_then(|n| n.text())
+        .unwrap_or_default()
+        .trim()
+        .to_string()
+}
+
+/** 按标签路径取数值，不存在或格式不对时返回 0 */
+fn number<T: std::str::FromStr + Default>(node: roxmltree::Node, path: &[&str]) -> T {
+    text(node, path).parse().unwrap_or_default()
+}
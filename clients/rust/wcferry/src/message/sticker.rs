@@ -0,0 +1,69 @@
+use log::warn;
+
+use super::{find, xml_body};
+
+/** 表情消息（type 47） */
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Sticker {
+    pub md5: String,
+    /** 表情下载地址 */
+    pub cdnurl: String,
+    pub width: u32,
+    pub height: u32,
+    /** 文件大小 */
+    pub len: u64,
+}
+
+impl Sticker {
+    /**
+     * 解析表情消息 XML
+     * @param xml: WxMsg.content（群消息可带 `wxid:\n` 前缀）
+     */
+    pub fn parse(xml: &str) -> Result<Sticker, Box<dyn std::error::Error>> {
+        let doc = match roxmltree::Document::parse(xml_body(xml)) {
+            Ok(doc) => doc,
+            Err(e) => {
+                warn!("表情消息解析失败: {}", e);
+                return Err("表情消息解析失败".into());
+            }
+        };
+        let emoji = match find(doc.root_element(), &["emoji"]) {
+            Some(node) => node,
+            None => {
+                warn!("emoji 节点不存在");
+                return Err("表情消息解析失败".into());
+            }
+        };
+        let attr = |name: &str| emoji.attribute(name).unwrap_or_default();
+        Ok(Sticker {
+            md5: attr("md5").to_string(),
+            cdnurl: attr("cdnurl").to_string(),
+            width: attr("width").parse().unwrap_or_default(),
+            height: attr("height").parse().unwrap_or_default(),
+            len: attr("len").parse().unwrap_or_default(),
+        })
+    }
+}
+
+mod test {
+
+    #[test]
+    fn test_parse_sticker() {
+        use crate::message::sticker::Sticker;
+
+        let xml = r#"wxid_abc:
+<msg><emoji fromusername="wxid_abc" tousername="34476879773@chatroom" type="2" idbuffer="media:0_0" md5="3c5a4e7e3ba2d6d5a3e8c4d2b1f0e9a7" len="24512" productid="" androidmd5="3c5a4e7e3ba2d6d5a3e8c4d2b1f0e9a7" androidlen="24512" cdnurl="http://wxapp.tc.qq.com/262/20304/stodownload?m=3c5a4e7e&amp;filekey=30350201" designerid="" thumburl="" width="240" height="180"></emoji></msg>"#;
+        assert_eq!(
+            Sticker::parse(xml).unwrap(),
+            Sticker {
+                md5: String::from("3c5a4e7e3ba2d6d5a3e8c4d2b1f0e9a7"),
+                cdnurl: String::from(
+                    "http://wxapp.tc.qq.com/262/20304/stodownload?m=3c5a4e7e&filekey=30350201"
+                ),
+                width: 240,
+                height: 180,
+                len: 24512,
+            }
+        );
+    }
+}
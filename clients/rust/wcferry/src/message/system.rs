@@ -0,0 +1,523 @@
+use std::collections::HashMap;
+
+use super::{find, text, xml_body};
+
+/** 系统消息中涉及的人；自己显示为「你」，wxid 仅在 sysmsg XML 中提供时才有 */
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Member {
+    pub wxid: String,
+    pub name: String,
+}
+
+impl Member {
+    /** 是否为自己 */
+    pub fn is_self(&self) -> bool {
+        self.wxid.is_empty() && (self.name == "你" || self.name == "我")
+    }
+}
+
+/** 系统消息（type 10000 / 10002）归类后的事件 */
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum SysEvent {
+    /** 邀请或扫码入群；扫码时 inviter 为二维码分享者 */
+    MemberJoined {
+        inviter: Option<Member>,
+        members: Vec<Member>,
+    },
+    /** 被移出群聊 */
+    MemberLeft {
+        operator: Option<Member>,
+        members: Vec<Member>,
+    },
+    GroupRenamed {
+        operator: Member,
+        name: String,
+    },
+    Pat {
+        from: Member,
+        to: Member,
+    },
+    RedPacket {
+        text: String,
+    },
+    FriendAdded {
+        name: String,
+    },
+    /** 未归类的系统消息，保留原文 */
+    Other(String),
+}
+
+impl SysEvent {
+    /**
+     * 解析系统消息
+     * @param content: WxMsg.content，纯文本或 sysmsg XML
+     */
+    pub fn parse(content: &str) -> SysEvent {
+        let body = xml_body(content);
+        if body.starts_with("<sysmsg") {
+            if let Some(event) = parse_sysmsg(body) {
+                return event;
+            }
+            return SysEvent::Other(content.to_string());
+        }
+        parse_text(content.trim(), &HashMap::new())
+    }
+}
+
+/** 解析 sysmsg XML：拍一拍直接取 wxid，模板消息按 link 取成员后再按文本归类 */
+fn parse_sysmsg(xml: &str) -> Option<SysEvent> {
+    let doc = roxmltree::Document::parse(xml).ok()?;
+    let root = doc.root_element();
+    match root.attribute("type") {
+        Some("pat") => {
+            let pat = find(root, &["pat"])?;
+            let member = |wxid: String| Member {
+                name: wxid.clone(),
+                wxid,
+            };
+            Some(SysEvent::Pat {
+                from: member(text(pat, &["fromusername"])),
+                to: member(text(pat, &["pattedusername"])),
+            })
+        }
+        Some("sysmsgtemplate") => {
+            let tmpl = find(root, &["sysmsgtemplate", "content_template"])?;
+            let mut links = HashMap::new();
+            if let Some(list) = find(tmpl, &["link_list"]) {
+                for link in list.children().filter(|n| n.has_tag_name("link")) {
+                    let mut members = vec![];
+                    if let Some(list) = find(link, &["memberlist"]) {
+                        for m in list.children().filter(|n| n.has_tag_name("member")) {
+                            members.push(Member {
+                                wxid: text(m, &["username"]),
+                                name: text(m, &["nickname"]),
+                            });
+                        }
+                    }
+                    let name = link.attribute("name").unwrap_or_default();
+                    links.insert(name.to_string(), members);
+                }
+            }
+            Some(parse_text(&text(tmpl, &["template"]), &links))
+        }
+        _ => None,
+    }
+}
+
+/** 昵称占位符：关键词只在引号外的文本中匹配，昵称里的「拍了拍」「将」等不影响归类 */
+const NAME: char = '\u{FFFC}';
+
+/** 一处引号中的昵称；模板中的 `$name$` 直接带有成员列表 */
+struct Slot {
+    raw: String,
+    members: Vec<Member>,
+}
+
+impl Slot {
+    /** 作为单个人时，多个名字不拆开 */
+    fn person(&self) -> Member {
+        match self.members.as_slice() {
+            [member] => member.clone(),
+            _ => named(&self.raw),
+        }
+    }
+}
+
+/** 把昵称替换为占位符后的文本，rendered 为展开模板后的原文 */
+#[derive(Default)]
+struct Skeleton {
+    text: String,
+    rendered: String,
+    slots: Vec<Slot>,
+}
+
+/** 关键词一侧的文本及其中的昵称 */
+struct Part<'a> {
+    text: &'a str,
+    slots: &'a [Slot],
+}
+
+impl Part<'_> {
+    /** 第一处昵称；没有引号时取引号外的文本（如「你」） */
+    fn person(&self) -> Member {
+        match self.slots.first() {
+            Some(slot) => slot.person(),
+            None => named(self.text.trim()),
+        }
+    }
+
+    fn members(&self) -> Vec<Member> {
+        self.slots
+            .first()
+            .map(|slot| slot.members.clone())
+            .unwrap_or_default()
+    }
+}
+
+impl Skeleton {
+    /**
+     * 拆出引号中的昵称
+     * @param text: 纯文本，或 sysmsg 模板（`$name$` 从 links 中取成员）
+     */
+    fn new(text: &str, links: &HashMap<String, Vec<Member>>) -> Skeleton {
+        let mut skeleton = Skeleton::default();
+        let mut rest = text;
+        while let Some(c) = rest.chars().next() {
+            let after = &rest[c.len_utf8()..];
+            let close = match c {
+                '"' => Some('"'),
+                '“' => Some('”'),
+                '$' => Some('$'),
+                _ => None,
+            };
+            let found = close.and_then(|close| Some((close, after.split_once(close)?)));
+            let slot = found.and_then(|(close, (inner, tail))| {
+                let link = |name: &str| {
+                    links.get(name).map(|members| Slot {
+                        raw: members
+                            .iter()
+                            .map(|m| m.name.as_str())
+                            .collect::<Vec<_>>()
+                            .join("、"),
+                        members: members.clone(),
+                    })
+                };
+                let slot = if c == '$' {
+                    link(inner)?
+                } else {
+                    let name = inner.strip_prefix('$').and_then(|n| n.strip_suffix('$'));
+                    match name.and_then(link) {
+                        Some(slot) => slot,
+                        // 纯文本中只能按「、」拆分多个成员
+                        None => Slot {
+                            raw: inner.to_string(),
+                            members: inner.split('、').map(named).collect(),
+                        },
+                    }
+                };
+                Some((close, slot, tail))
+            });
+            match slot {
+                Some((close, slot, tail)) => {
+                    if c == '$' {
+                        skeleton.rendered.push_str(&slot.raw);
+                    } else {
+                        skeleton.rendered.push(c);
+                        skeleton.rendered.push_str(&slot.raw);
+                        skeleton.rendered.push(close);
+                    }
+                    skeleton.text.push(NAME);
+                    skeleton.slots.push(slot);
+                    rest = tail;
+                }
+                None => {
+                    skeleton.rendered.push(c);
+                    if c != NAME {
+                        skeleton.text.push(c);
+                    }
+                    rest = after;
+                }
+            }
+        }
+        skeleton
+    }
+
+    fn whole(&self) -> Part<'_> {
+        Part {
+            text: &self.text,
+            slots: &self.slots,
+        }
+    }
+
+    /** 在引号外的文本中按关键词切分 */
+    fn split_once(&self, keyword: &str) -> Option<(Part<'_>, Part<'_>)> {
+        let (left, right) = self.text.split_once(keyword)?;
+        let n = left.matches(NAME).count();
+        Some((
+            Part {
+                text: left,
+                slots: &self.slots[..n],
+            },
+            Part {
+                text: right,
+                slots: &self.slots[n..],
+            },
+        ))
+    }
+}
+
+fn named(name: &str) -> Member {
+    Member {
+        wxid: String::new(),
+        name: name.to_string(),
+    }
+}
+
+/** 按文本归类，links 为 sysmsg 模板中 `$name$` 对应的成员 */
+fn parse_text(text: &str, links: &HashMap<String, Vec<Member>>) -> SysEvent {
+    let skeleton = Skeleton::new(text, links);
+    let text = skeleton.rendered.as_str();
+
+    // 先匹配固定开头的文本，好友备注不带引号
+    if let Some(rest) = text.strip_prefix("你已添加了") {
+        let name = rest.split('，').next().unwrap_or(rest).trim();
+        return SysEvent::FriendAdded {
+            name: name.to_string(),
+        };
+    }
+    if let Some((left, right)) = skeleton.split_once("拍了拍") {
+        let to = match right.slots.first() {
+            Some(slot) => slot.person(),
+            None if right.text.trim_start().starts_with('我') => named("我"),
+            None => named(right.text.trim()),
+        };
+        return SysEvent::Pat {
+            from: left.person(),
+            to,
+        };
+    }
+    if skeleton.text.contains("加入了群聊") || skeleton.text.contains("加入群聊") {
+        if let Some((left, right)) = skeleton.split_once("通过扫描") {
+            return SysEvent::MemberJoined {
+                inviter: right.slots.first().map(Slot::person),
+                members: left.members(),
+            };
+        }
+        if let Some((left, right)) = skeleton.split_once("邀请") {
+            let members = if right.text.starts_with('你') {
+                vec![named("你")]
+            } else {
+                right.members()
+            };
+            return SysEvent::MemberJoined {
+                inviter: Some(left.person()),
+                members,
+            };
+        }
+    }
+    if skeleton.text.contains("移出了群聊") || skeleton.text.contains("移出群聊") {
+        if let Some((left, right)) = skeleton.split_once("将") {
+            return SysEvent::MemberLeft {
+                operator: Some(left.person()),
+                members: right.members(),
+            };
+        }
+        // 你被"张三"移出群聊
+        if let Some((left, right)) = skeleton.split_once("被") {
+            let members = match left.slots.first() {
+                Some(slot) => slot.members.clone(),
+                None => vec![named(left.text.trim())],
+            };
+            return SysEvent::MemberLeft {
+                operator: Some(right.person()),
+                members,
+            };
+        }
+        return SysEvent::MemberLeft {
+            operator: None,
+            members: skeleton.whole().members(),
+        };
+    }
+    if let Some((left, right)) = skeleton.split_once("修改群名为") {
+        let name = match right.slots.first() {
+            Some(slot) => slot.raw.clone(),
+            None => right.text.trim().to_string(),
+        };
+        return SysEvent::GroupRenamed {
+            operator: left.person(),
+            name,
+        };
+    }
+    if skeleton.text.contains("红包") {
+        return SysEvent::RedPacket {
+            text: text.to_string(),
+        };
+    }
+    SysEvent::Other(text.to_string())
+}
+
+mod test {
+
+    #[test]
+    fn test_parse_member_joined() {
+        use crate::message::system::{Member, SysEvent};
+
+        let named = |name: &str| Member {
+            wxid: String::new(),
+            name: String::from(name),
+        };
+        assert_eq!(
+            SysEvent::parse(r#""张三"邀请"李四、王五"加入了群聊"#),
+            SysEvent::MemberJoined {
+                inviter: Some(named("张三")),
+                members: vec![named("李四"), named("王五")],
+            }
+        );
+        assert_eq!(
+            SysEvent::parse(r#"你邀请"李四"加入了群聊  "#),
+            SysEvent::MemberJoined {
+                inviter: Some(named("你")),
+                members: vec![named("李四")],
+            }
+        );
+        assert_eq!(
+            SysEvent::parse(r#""李四"通过扫描"张三"分享的二维码加入群聊"#),
+            SysEvent::MemberJoined {
+                inviter: Some(named("张三")),
+                members: vec![named("李四")],
+            }
+        );
+        assert!(matches!(
+            SysEvent::parse(r#""张三"邀请你加入了群聊，群聊参与人还有：李四"#),
+            SysEvent::MemberJoined { members, .. } if members[0].is_self()
+        ));
+    }
+
+    #[test]
+    fn test_parse_member_left_and_rename() {
+        use crate::message::system::SysEvent;
+
+        match SysEvent::parse(r#"你将"李四"移出了群聊"#) {
+            SysEvent::MemberLeft { operator, members } => {
+                assert!(operator.unwrap().is_self());
+                assert_eq!(members[0].name, "李四");
+            }
+            other => panic!("unexpected: {:?}", other),
+        }
+        match SysEvent::parse(r#""李四"移出了群聊"#) {
+            SysEvent::MemberLeft { operator, members } => {
+                assert!(operator.is_none());
+                assert_eq!(members[0].name, "李四");
+            }
+            other => panic!("unexpected: {:?}", other),
+        }
+        match SysEvent::parse("\"张三\"修改群名为“周末爬山群”") {
+            SysEvent::GroupRenamed { operator, name } => {
+                assert_eq!(operator.name, "张三");
+                assert_eq!(name, "周末爬山群");
+            }
+            other => panic!("unexpected: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_parse_pat_and_others() {
+        use crate::message::system::SysEvent;
+
+        match SysEvent::parse(r#""张三" 拍了拍 "李四" 的肩膀"#) {
+            SysEvent::Pat { from, to } => {
+                assert_eq!(from.name, "张三");
+                assert_eq!(to.name, "李四");
+            }
+            other => panic!("unexpected: {:?}", other),
+        }
+        match SysEvent::parse(r#""张三" 拍了拍我"#) {
+            SysEvent::Pat { to, .. } => assert!(to.is_self()),
+            other => panic!("unexpected: {:?}", other),
+        }
+        assert!(matches!(
+            SysEvent::parse("收到红包，请在手机上查看"),
+            SysEvent::RedPacket { .. }
+        ));
+        assert_eq!(
+            SysEvent::parse("你已添加了张三，现在可以开始聊天了。"),
+            SysEvent::FriendAdded {
+                name: String::from("张三")
+            }
+        );
+        assert_eq!(
+            SysEvent::parse("你已添加了抢红包拍了拍，现在可以开始聊天了。"),
+            SysEvent::FriendAdded {
+                name: String::from("抢红包拍了拍")
+            }
+        );
+        assert_eq!(
+            SysEvent::parse("以上是打招呼的内容"),
+            SysEvent::Other(String::from("以上是打招呼的内容"))
+        );
+    }
+
+    #[test]
+    fn test_parse_keyword_nicknames() {
+        use crate::message::system::SysEvent;
+
+        match SysEvent::parse(r#""张三"邀请"拍了拍"加入了群聊"#) {
+            SysEvent::MemberJoined { inviter, members } => {
+                assert_eq!(inviter.unwrap().name, "张三");
+                assert_eq!(members[0].name, "拍了拍");
+            }
+            other => panic!("unexpected: {:?}", other),
+        }
+        match SysEvent::parse(r#""大将"将"李四"移出了群聊"#) {
+            SysEvent::MemberLeft { operator, members } => {
+                assert_eq!(operator.unwrap().name, "大将");
+                assert_eq!(members[0].name, "李四");
+            }
+            other => panic!("unexpected: {:?}", other),
+        }
+        match SysEvent::parse(r#"你被"被动邀请"移出群聊"#) {
+            SysEvent::MemberLeft { operator, members } => {
+                assert_eq!(operator.unwrap().name, "被动邀请");
+                assert!(members[0].is_self());
+            }
+            other => panic!("unexpected: {:?}", other),
+        }
+        match SysEvent::parse(r#""抢红包"修改群名为"加入了群聊""#) {
+            SysEvent::GroupRenamed { operator, name } => {
+                assert_eq!(operator.name, "抢红包");
+                assert_eq!(name, "加入了群聊");
+            }
+            other => panic!("unexpected: {:?}", other),
+        }
+        match SysEvent::parse(r#""邀请"通过扫描"红包"分享的二维码加入群聊"#) {
+            SysEvent::MemberJoined { inviter, members } => {
+                assert_eq!(inviter.unwrap().name, "红包");
+                assert_eq!(members[0].name, "邀请");
+            }
+            other => panic!("unexpected: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_parse_sysmsg() {
+        use crate::message::system::{Member, SysEvent};
+
+        let xml = r#"<sysmsg type="pat"><pat><fromusername>wxid_a</fromusername><chatusername>34476879773@chatroom</chatusername><pattedusername>wxid_b</pattedusername><template><![CDATA["${wxid_a}" 拍了拍 "${wxid_b}"]]></template></pat></sysmsg>"#;
+        match SysEvent::parse(xml) {
+            SysEvent::Pat { from, to } => {
+                assert_eq!(from.wxid, "wxid_a");
+                assert_eq!(to.wxid, "wxid_b");
+            }
+            other => panic!("unexpected: {:?}", other),
+        }
+
+        let xml = r#"<sysmsg type="sysmsgtemplate"><sysmsgtemplate><content_template type="tmpl_type_profile">
+            <plain><![CDATA[]]></plain><template><![CDATA["$username$"邀请"$names$"加入了群聊]]></template>
+            <link_list>
+            <link name="username" type="link_profile"><memberlist><member><username><![CDATA[wxid_a]]></username><nickname><![CDATA[张三]]></nickname></member></memberlist></link>
+            <link name="names" type="link_profile"><memberlist>
+            <member><username><![CDATA[wxid_b]]></username><nickname><![CDATA[李四]]></nickname></member>
+            <member><username><![CDATA[wxid_c]]></username><nickname><![CDATA[王五]]></nickname></member>
+            </memberlist><separator><![CDATA[、]]></separator></link>
+            </link_list></content_template></sysmsgtemplate></sysmsg>"#;
+        let member = |wxid: &str, name: &str| Member {
+            wxid: String::from(wxid),
+            name: String::from(name),
+        };
+        assert_eq!(
+            SysEvent::parse(xml),
+            SysEvent::MemberJoined {
+                inviter: Some(member("wxid_a", "张三")),
+                members: vec![member("wxid_b", "李四"), member("wxid_c", "王五")],
+            }
+        );
+        let xml = xml.replace("李四", "李、四");
+        assert_eq!(
+            SysEvent::parse(&xml),
+            SysEvent::MemberJoined {
+                inviter: Some(member("wxid_a", "张三")),
+                members: vec![member("wxid_b", "李、四"), member("wxid_c", "王五")],
+            }
+        );
+    }
+}
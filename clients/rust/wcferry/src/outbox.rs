@@ -0,0 +1,483 @@
+use std::{
+    collections::HashSet,
+    fs,
+    path::{Path, PathBuf},
+    sync::atomic::{AtomicBool, Ordering},
+    thread,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use log::{error, info, warn};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    error::WcfError,
+    receiver::{Receiver, Wxid},
+    status::SendStatus,
+    wechat::{self, WeChat},
+};
+
+/** 默认最大尝试次数 */
+const DEFAULT_MAX_ATTEMPTS: u32 = 10;
+/** 默认首次重试间隔 */
+const DEFAULT_BASE_BACKOFF: Duration = Duration::from_secs(2);
+/** 默认最大重试间隔 */
+const DEFAULT_MAX_BACKOFF: Duration = Duration::from_secs(300);
+
+/** 待发送内容 */
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Payload {
+    Text {
+        msg: String,
+        #[serde(default)]
+        aters: Vec<String>,
+    },
+    Image {
+        path: PathBuf,
+    },
+    File {
+        path: PathBuf,
+    },
+    Xml {
+        xml: String,
+        path: Option<PathBuf>,
+        xml_type: i32,
+    },
+    Emotion {
+        path: PathBuf,
+    },
+}
+
+impl Payload {
+    pub fn text(msg: &str, aters: &[Wxid]) -> Self {
+        Payload::Text {
+            msg: msg.to_string(),
+            aters: aters.iter().map(|wxid| wxid.to_string()).collect(),
+        }
+    }
+
+    /** 通过对应的 send_* 发送 */
+    pub fn send(
+        &self,
+        wechat: &mut WeChat,
+        receiver: impl Into<Receiver>,
+    ) -> Result<SendStatus, WcfError> {
+        match self {
+            Payload::Text { msg, aters } => {
+                let aters = aters
+                    .iter()
+                    .map(|wxid| wxid.parse())
+                    .collect::<Result<Vec<Wxid>, _>>()
+                    .map_err(|e| WcfError::InvalidArgument(e.to_string()))?;
+                wechat::send_text(wechat, msg.clone(), receiver, &aters)
+            }
+            Payload::Image { path } => wechat::send_image(wechat, path.clone(), receiver),
+            Payload::File { path } => wechat::send_file(wechat, path.clone(), receiver),
+            Payload::Xml {
+                xml,
+                path,
+                xml_type,
+            } => wechat::send_xml(wechat, xml.clone(), path.clone(), receiver, *xml_type),
+            Payload::Emotion { path } => wechat::send_emotion(wechat, path.clone(), receiver),
+        }
+    }
+}
+
+/** 队列项状态 */
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ItemStatus {
+    /** 等待发送或等待重试 */
+    Pending,
+    Sent,
+    /** 服务端拒绝或重试次数用尽，不再发送 */
+    Failed,
+}
+
+/** 队列中的一条消息 */
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct OutboxItem {
+    pub id: u64,
+    pub receiver: String,
+    pub payload: Payload,
+    pub status: ItemStatus,
+    /** 已尝试次数 */
+    pub attempts: u32,
+    /** 入队时间，毫秒时间戳 */
+    pub created_at: u64,
+    /** 下次可尝试的时间，毫秒时间戳 */
+    pub next_attempt_at: u64,
+    pub last_error: Option<String>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct Store {
+    next_id: u64,
+    items: Vec<OutboxItem>,
+}
+
+/** 单次投递结果 */
+enum Outcome {
+    Sent,
+    /** 服务端明确拒绝，重试无意义 */
+    Rejected(String),
+    /** 通信失败，稍后重试 */
+    Retry(String),
+    /** 被限流，推迟发送，不计入尝试次数 */
+    Deferred,
+}
+
+/**
+ * 持久化的发送队列
+ * 服务不可用或账号未登录时消息暂存在本地文件中，登录后按接收人顺序依次发送，
+ * 请求未发出时按指数退避重试
+ * 请求已发出但未收到响应（WcfError::Timeout）时消息可能已经发出，不会重试，标记为失败，
+ * 由调用方核实后决定是否重新入队；服务端在发出消息后、返回前异常时仍可能重复发送，
+ * 需要严格去重时请配合 EchoTracker 确认
+ * @example let mut outbox = Outbox::open("outbox.json")?; outbox.push(receiver, Payload::text("hi", &[]))?; outbox.flush(&mut wechat)?;
+ */
+#[derive(Debug)]
+pub struct Outbox {
+    path: PathBuf,
+    store: Store,
+    max_attempts: u32,
+    base_backoff: Duration,
+    max_backoff: Duration,
+}
+
+fn now_millis() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or_default()
+}
+
+impl Outbox {
+    /** 打开队列文件，不存在时新建 */
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, Box<dyn std::error::Error>> {
+        let path = path.as_ref().to_path_buf();
+        let store = if path.exists() {
+            let data = match fs::read(&path) {
+                Ok(data) => data,
+                Err(e) => {
+                    error!("读取发送队列失败: {}, {}", path.display(), e);
+                    return Err("打开发送队列失败".into());
+                }
+            };
+            match serde_json::from_slice(&data) {
+                Ok(store) => store,
+                Err(e) => {
+                    error!("发送队列解析失败: {}, {}", path.display(), e);
+                    return Err("打开发送队列失败".into());
+                }
+            }
+        } else {
+            Store::default()
+        };
+        Ok(Outbox {
+            path,
+            store,
+            max_attempts: DEFAULT_MAX_ATTEMPTS,
+            base_backoff: DEFAULT_BASE_BACKOFF,
+            max_backoff: DEFAULT_MAX_BACKOFF,
+        })
+    }
+
+    /** 最大尝试次数，0 表示不限 */
+    pub fn max_attempts(mut self, max_attempts: u32) -> Self {
+        self.max_attempts = max_attempts;
+        self
+    }
+
+    /** 重试间隔从 base 开始翻倍，不超过 max */
+    pub fn backoff(mut self, base: Duration, max: Duration) -> Self {
+        self.base_backoff = base;
+        self.max_backoff = max;
+        self
+    }
+
+    /**
+     * 加入队列并立即落盘
+     * @return 队列项 id，可用于查询状态
+     */
+    pub fn push(
+        &mut self,
+        receiver: impl Into<Receiver>,
+        payload: Payload,
+    ) -> Result<u64, Box<dyn std::error::Error>> {
+        let receiver = receiver.into();
+        if let Payload::Text { aters, .. } = &payload {
+            if !aters.is_empty() && !receiver.is_room() {
+                error!("私聊不能 @: {}", receiver);
+                return Err("加入发送队列失败".into());
+            }
+        }
+        self.store.next_id += 1;
+        let id = self.store.next_id;
+        let now = now_millis();
+        self.store.items.push(OutboxItem {
+            id,
+            receiver: receiver.to_string(),
+            payload,
+            status: ItemStatus::Pending,
+            attempts: 0,
+            created_at: now,
+            next_attempt_at: now,
+            last_error: None,
+        });
+        self.save()?;
+        Ok(id)
+    }
+
+    pub fn get(&self, id: u64) -> Option<&OutboxItem> {
+        self.store.items.iter().find(|item| item.id == id)
+    }
+
+    pub fn status(&self, id: u64) -> Option<ItemStatus> {
+        self.get(id).map(|item| item.status)
+    }
+
+    pub fn items(&self) -> &[OutboxItem] {
+        &self.store.items
+    }
+
+    pub fn pending(&self) -> usize {
+        self.store
+            .items
+            .iter()
+            .filter(|item| item.status == ItemStatus::Pending)
+            .count()
+    }
+
+    /** 将失败的队列项重新置为待发送 */
+    pub fn retry(&mut self, id: u64) -> Result<bool, Box<dyn std::error::Error>> {
+        let item = match self.store.items.iter_mut().find(|item| item.id == id) {
+            Some(item) if item.status == ItemStatus::Failed => item,
+            _ => return Ok(false),
+        };
+        item.status = ItemStatus::Pending;
+        item.attempts = 0;
+        item.next_attempt_at = now_millis();
+        self.save()?;
+        Ok(true)
+    }
+
+    /** 移除已发送的队列项 */
+    pub fn purge_sent(&mut self) -> Result<usize, Box<dyn std::error::Error>> {
+        let before = self.store.items.len();
+        self.store
+            .items
+            .retain(|item| item.status != ItemStatus::Sent);
+        let removed = before - self.store.items.len();
+        if removed > 0 {
+            self.save()?;
+        }
+        Ok(removed)
+    }
+
+    /**
+     * 发送所有到期的队列项
+     * 同一接收人的消息严格按入队顺序发送，前一条未成功（包括被拒绝）时后面的本轮不会发出
+     * 状态变为已发送或失败时立即保存，避免崩溃后重复发送；只调整重试时间的改动在本轮结束时保存
+     * @return 本次发送成功的条数；未登录或服务不可用时为 0
+     */
+    pub fn flush(&mut self, wechat: &mut WeChat) -> Result<usize, Box<dyn std::error::Error>> {
+        if self.pending() == 0 {
+            return Ok(0);
+        }
+        match wechat::is_login(wechat) {
+            Ok(true) => {}
+            Ok(false) => {
+                info!("未登录，发送队列暂停");
+                return Ok(0);
+            }
+            Err(e) => {
+                warn!("服务不可用，发送队列暂停: {}", e);
+                return Ok(0);
+            }
+        }
+
+        let mut sent = 0;
+        let mut dirty = false;
+        let mut blocked: HashSet<String> = HashSet::new();
+        for index in 0..self.store.items.len() {
+            let item = &self.store.items[index];
+            if item.status != ItemStatus::Pending || blocked.contains(&item.receiver) {
+                continue;
+            }
+            if item.next_attempt_at > now_millis() {
+                blocked.insert(item.receiver.clone());
+                continue;
+            }
+            let outcome = deliver(wechat, &item.receiver, &item.payload);
+            let item = &mut self.store.items[index];
+            dirty = true;
+            if !matches!(outcome, Outcome::Deferred) {
+                item.attempts += 1;
+            }
+            match outcome {
+                Outcome::Sent => {
+                    item.status = ItemStatus::Sent;
+                    item.last_error = None;
+                    sent += 1;
+                }
+                Outcome::Rejected(e) => {
+                    warn!("发送队列项 {} 被拒绝: {}", item.id, e);
+                    item.status = ItemStatus::Failed;
+                    item.last_error = Some(e);
+                    blocked.insert(item.receiver.clone());
+                }
+                Outcome::Retry(e) => {
+                    if self.max_attempts != 0 && item.attempts >= self.max_attempts {
+                        warn!("发送队列项 {} 重试次数用尽: {}", item.id, e);
+                        item.status = ItemStatus::Failed;
+                        blocked.insert(item.receiver.clone());
+                    } else {
+                        let backoff = backoff(self.base_backoff, self.max_backoff, item.attempts);
+                        item.next_attempt_at = now_millis() + backoff.as_millis() as u64;
+                        blocked.insert(item.receiver.clone());
+                    }
+                    item.last_error = Some(e);
+                }
+                Outcome::Deferred => {
+                    item.next_attempt_at = now_millis() + self.base_backoff.as_millis() as u64;
+                    blocked.insert(item.receiver.clone());
+                }
+            }
+            if item.status != ItemStatus::Pending {
+                self.save()?;
+                dirty = false;
+            }
+        }
+        if dirty {
+            self.save()?;
+        }
+        Ok(sent)
+    }
+
+    /**
+     * 循环发送队列，直到 stop 被置为 true
+     * @param interval: 每轮之间的等待时间
+     */
+    pub fn run(
+        &mut self,
+        wechat: &mut WeChat,
+        interval: Duration,
+        stop: &AtomicBool,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        while !stop.load(Ordering::Relaxed) {
+            self.flush(wechat)?;
+            thread::sleep(interval);
+        }
+        Ok(())
+    }
+
+    /** 先写临时文件再改名，避免写一半时崩溃损坏队列 */
+    fn save(&self) -> Result<(), Box<dyn std::error::Error>> {
+        let data = serde_json::to_vec_pretty(&self.store)?;
+        let tmp = self.path.with_extension("tmp");
+        let result = fs::write(&tmp, data).and_then(|_| fs::rename(&tmp, &self.path));
+        match result {
+            Ok(()) => Ok(()),
+            Err(e) => {
+                error!("保存发送队列失败: {}, {}", self.path.display(), e);
+                Err("保存发送队列失败".into())
+            }
+        }
+    }
+}
+
+/** 第 attempts 次失败后的等待时间 */
+fn backoff(base: Duration, max: Duration, attempts: u32) -> Duration {
+    let factor = 1u32
+        .checked_shl(attempts.saturating_sub(1))
+        .unwrap_or(u32::MAX);
+    base.saturating_mul(factor).min(max)
+}
+
+fn deliver(wechat: &mut WeChat, receiver: &str, payload: &Payload) -> Outcome {
+    let receiver: Receiver = match receiver.parse() {
+        Ok(receiver) => receiver,
+        Err(e) => {
+            warn!("{}", e);
+            return Outcome::Rejected(SendStatus::ReceiverInvalid.to_string());
+        }
+    };
+    match payload.send(wechat, receiver) {
+        Ok(SendStatus::Success) => Outcome::Sent,
+        Ok(SendStatus::NotLoggedIn) => Outcome::Retry(SendStatus::NotLoggedIn.to_string()),
+        Ok(status) => Outcome::Rejected(status.to_string()),
+        Err(WcfError::RateLimited) => Outcome::Deferred,
+        Err(e) if e.is_retryable() => Outcome::Retry(e.to_string()),
+        Err(e) => Outcome::Rejected(e.to_string()),
+    }
+}
+
+mod test {
+
+    #[test]
+    fn test_persist() {
+        use crate::outbox::{ItemStatus, Outbox, Payload};
+        use crate::receiver::{RoomId, SpecialAccount, Wxid};
+
+        let path = std::env::temp_dir().join(format!("wcferry-outbox-{}.json", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+
+        let mut outbox = Outbox::open(&path).unwrap();
+        let roomid: RoomId = "34476879773@chatroom".parse().unwrap();
+        let wxid: Wxid = "wxid_abc".parse().unwrap();
+        let first = outbox
+            .push(
+                roomid,
+                Payload::text("hello @abc", std::slice::from_ref(&wxid)),
+            )
+            .unwrap();
+        let second = outbox
+            .push(
+                SpecialAccount::FileHelper,
+                Payload::File {
+                    path: std::path::PathBuf::from("C:\\a.pdf"),
+                },
+            )
+            .unwrap();
+        assert!(outbox.push(wxid, Payload::text("hi", &[])).is_ok());
+        let private: Wxid = "wxid_def".parse().unwrap();
+        let ater: Wxid = "wxid_abc".parse().unwrap();
+        assert!(outbox.push(private, Payload::text("hi", &[ater])).is_err());
+
+        let reopened = Outbox::open(&path).unwrap();
+        assert_eq!(reopened.items(), outbox.items());
+        assert_eq!(reopened.pending(), 3);
+        assert_eq!(reopened.status(first), Some(ItemStatus::Pending));
+        assert_eq!(reopened.get(second).unwrap().receiver, "filehelper");
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[test]
+    fn test_backoff() {
+        use std::time::Duration;
+
+        let base = Duration::from_secs(2);
+        let max = Duration::from_secs(60);
+        assert_eq!(crate::outbox::backoff(base, max, 1), Duration::from_secs(2));
+        assert_eq!(crate::outbox::backoff(base, max, 3), Duration::from_secs(8));
+        assert_eq!(crate::outbox::backoff(base, max, 10), max);
+        assert_eq!(crate::outbox::backoff(base, max, 100), max);
+    }
+
+    #[test]
+    fn test_flush() {
+        let mut wechat = crate::wechat::WeChat::default();
+        let path = std::env::temp_dir().join("wcferry-outbox-flush.json");
+        let mut outbox = crate::outbox::Outbox::open(&path).unwrap();
+        outbox
+            .push(
+                crate::receiver::SpecialAccount::FileHelper,
+                crate::outbox::Payload::text("Hello, wcferry!", &[]),
+            )
+            .unwrap();
+        let sent = outbox.flush(&mut wechat).unwrap();
+        println!("Sent: {}", sent);
+    }
+}
@@ -0,0 +1,214 @@
+use std::path::{Path, PathBuf};
+
+/**
+ * 客户端与服务端之间的路径映射
+ * 客户端在 Linux 上、服务端在 Windows 上时，通过共享目录交换文件，例如
+ * `/mnt/wx-share` <-> `C:\share` 或 `\\server\share`
+ */
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct PathMapper {
+    rules: Vec<PathRule>,
+}
+
+/** 一条前缀映射规则 */
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PathRule {
+    /** 客户端本地前缀，如 /mnt/wx-share */
+    pub local: String,
+    /** 服务端前缀，如 C:\share 或 \\server\share */
+    pub remote: String,
+}
+
+fn is_sep(c: char) -> bool {
+    c == '/' || c == '\\'
+}
+
+/** 去掉末尾分隔符（保留根目录本身） */
+fn trim_sep(s: &str) -> &str {
+    let trimmed = s.trim_end_matches(is_sep);
+    if trimmed.is_empty() {
+        &s[..s.len().min(1)]
+    } else {
+        trimmed
+    }
+}
+
+/**
+ * 判断 path 是否以 prefix 开头（按路径段边界），返回剩余部分
+ * @param ignore_case: Windows 路径不区分大小写
+ */
+fn strip_prefix<'a>(path: &'a str, prefix: &str, ignore_case: bool) -> Option<&'a str> {
+    let prefix = trim_sep(prefix);
+    let mut path_chars = path.char_indices();
+    for p in prefix.chars() {
+        let (_, c) = path_chars.next()?;
+        let same = if is_sep(p) && is_sep(c) {
+            true
+        } else if ignore_case {
+            p.to_lowercase().eq(c.to_lowercase())
+        } else {
+            p == c
+        };
+        if !same {
+            return None;
+        }
+    }
+    let rest = match path_chars.next() {
+        Some((i, _)) => &path[i..],
+        None => "",
+    };
+    if rest.is_empty() || rest.starts_with(is_sep) || prefix.ends_with(is_sep) {
+        Some(rest.trim_start_matches(is_sep))
+    } else {
+        None
+    }
+}
+
+impl PathMapper {
+    pub fn new() -> Self {
+        PathMapper::default()
+    }
+
+    /** 添加一条映射规则，匹配时取最长前缀 */
+    pub fn rule(mut self, local: &str, remote: &str) -> Self {
+        self.rules.push(PathRule {
+            local: local.to_string(),
+            remote: remote.to_string(),
+        });
+        self
+    }
+
+    pub fn rules(&self) -> &[PathRule] {
+        &self.rules
+    }
+
+    /** 客户端路径 -> 服务端路径；没有匹配的规则时原样返回 */
+    pub fn to_remote(&self, local: &Path) -> String {
+        let local = local.to_string_lossy();
+        let matched = self
+            .rules
+            .iter()
+            .filter_map(|rule| strip_prefix(&local, &rule.local, false).map(|rest| (rule, rest)))
+            .max_by_key(|(rule, _)| trim_sep(&rule.local).len());
+        match matched {
+            Some((rule, rest)) => join(trim_sep(&rule.remote), rest, '\\'),
+            None => local.to_string(),
+        }
+    }
+
+    /** 客户端路径是否被某条规则覆盖，即服务端写到该路径下的文件客户端能否读到 */
+    pub fn maps_local(&self, local: &Path) -> bool {
+        let local = local.to_string_lossy();
+        self.rules
+            .iter()
+            .any(|rule| strip_prefix(&local, &rule.local, false).is_some())
+    }
+
+    /** 服务端路径 -> 客户端路径；没有匹配的规则时原样返回 */
+    pub fn to_local(&self, remote: &str) -> PathBuf {
+        let matched = self
+            .rules
+            .iter()
+            .filter_map(|rule| strip_prefix(remote, &rule.remote, true).map(|rest| (rule, rest)))
+            .max_by_key(|(rule, _)| trim_sep(&rule.remote).len());
+        match matched {
+            Some((rule, rest)) => {
+                PathBuf::from(join(trim_sep(&rule.local), rest, std::path::MAIN_SEPARATOR))
+            }
+            None => PathBuf::from(remote),
+        }
+    }
+
+    /** 映射服务端返回的路径字段，空字符串保持不变 */
+    pub fn map_incoming(&self, remote: &mut String) {
+        if !remote.is_empty() {
+            *remote = self.to_local(remote).to_string_lossy().to_string();
+        }
+    }
+}
+
+/** 拼接前缀与剩余部分，并统一分隔符 */
+fn join(prefix: &str, rest: &str, sep: char) -> String {
+    let rest: String = rest
+        .chars()
+        .map(|c| if is_sep(c) { sep } else { c })
+        .collect();
+    if rest.is_empty() {
+        return prefix.to_string();
+    }
+    if prefix.ends_with(is_sep) {
+        format!("{}{}", prefix, rest)
+    } else {
+        format!("{}{}{}", prefix, sep, rest)
+    }
+}
+
+mod test {
+
+    #[test]
+    fn test_to_remote() {
+        use std::path::Path;
+
+        use crate::path_mapper::PathMapper;
+
+        let mapper = PathMapper::new()
+            .rule("/mnt/wx-share", "C:\\share")
+            .rule("/mnt/wx-share/images/", "D:\\images")
+            .rule("/mnt/unc", "\\\\server\\share");
+        assert_eq!(
+            mapper.to_remote(Path::new("/mnt/wx-share/a/b.jpg")),
+            "C:\\share\\a\\b.jpg"
+        );
+        assert_eq!(
+            mapper.to_remote(Path::new("/mnt/wx-share/images/1.png")),
+            "D:\\images\\1.png"
+        );
+        assert_eq!(
+            mapper.to_remote(Path::new("/mnt/unc/x.pdf")),
+            "\\\\server\\share\\x.pdf"
+        );
+        assert_eq!(mapper.to_remote(Path::new("/mnt/wx-share")), "C:\\share");
+        assert_eq!(
+            mapper.to_remote(Path::new("/mnt/wx-shared/a.jpg")),
+            "/mnt/wx-shared/a.jpg"
+        );
+        assert_eq!(
+            mapper.to_remote(Path::new("C:\\Users\\1.jpg")),
+            "C:\\Users\\1.jpg"
+        );
+        assert!(mapper.maps_local(Path::new("/mnt/wx-share/dl")));
+        assert!(!mapper.maps_local(Path::new("/mnt/wx-shared")));
+        assert!(!PathMapper::new().maps_local(Path::new("/tmp/wcferry")));
+    }
+
+    #[test]
+    fn test_to_local() {
+        use std::path::PathBuf;
+
+        use crate::path_mapper::PathMapper;
+
+        let mapper = PathMapper::new()
+            .rule("/mnt/wx-share", "C:\\share\\")
+            .rule("/mnt/unc", "\\\\server\\share");
+        assert_eq!(
+            mapper.to_local("c:\\Share\\wxid_abc\\FileStorage\\a.dat"),
+            PathBuf::from("/mnt/wx-share/wxid_abc/FileStorage/a.dat")
+        );
+        assert_eq!(
+            mapper.to_local("C:/share/a.dat"),
+            PathBuf::from("/mnt/wx-share/a.dat")
+        );
+        assert_eq!(
+            mapper.to_local("\\\\SERVER\\share\\x.pdf"),
+            PathBuf::from("/mnt/unc/x.pdf")
+        );
+        assert_eq!(
+            mapper.to_local("D:\\other\\a.dat"),
+            PathBuf::from("D:\\other\\a.dat")
+        );
+
+        let mut empty = String::new();
+        mapper.map_incoming(&mut empty);
+        assert!(empty.is_empty());
+    }
+}
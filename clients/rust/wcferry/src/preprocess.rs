@@ -0,0 +1,606 @@
+use std::{borrow::Cow, fs, io::Cursor, path::Path};
+
+use image::{
+    codecs::{
+        gif::{GifDecoder, GifEncoder, Repeat},
+        jpeg::JpegEncoder,
+    },
+    imageops::FilterType,
+    AnimationDecoder, DynamicImage, Frame, ImageFormat, ImageOutputFormat,
+};
+use log::{error, info, warn};
+
+use crate::{
+    error::WcfError,
+    media::{self, Media},
+    receiver::Receiver,
+    status::SendStatus,
+    wechat::{self, WeChat},
+};
+
+/** 超出大小时最多压缩的轮数 */
+const MAX_ROUNDS: usize = 6;
+/** JPEG 压缩质量下限，再低就改为缩小尺寸 */
+const MIN_JPEG_QUALITY: u8 = 50;
+
+/** 预处理参数 */
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ImageOptions {
+    /** 最长边像素 */
+    pub max_dimension: u32,
+    /** 文件大小上限 */
+    pub max_bytes: usize,
+    /** 转为 JPEG 时的初始质量 */
+    pub jpeg_quality: u8,
+}
+
+impl ImageOptions {
+    /** 图片消息默认参数：最长边 4096，10 MB */
+    pub fn image() -> Self {
+        ImageOptions {
+            max_dimension: 4096,
+            max_bytes: 10 * 1024 * 1024,
+            jpeg_quality: 85,
+        }
+    }
+
+    /** 表情默认参数：最长边 1024，1 MB */
+    pub fn emotion() -> Self {
+        ImageOptions {
+            max_dimension: 1024,
+            max_bytes: 1024 * 1024,
+            jpeg_quality: 85,
+        }
+    }
+}
+
+/** 预处理结果，无需处理时 data 借用原始数据 */
+#[derive(Debug)]
+pub struct Processed<'a> {
+    pub data: Cow<'a, [u8]>,
+    pub format: ImageFormat,
+}
+
+impl Processed<'_> {
+    pub fn is_changed(&self) -> bool {
+        matches!(self.data, Cow::Owned(_))
+    }
+
+    pub fn extension(&self) -> &'static str {
+        match self.format {
+            ImageFormat::Png => "png",
+            ImageFormat::Gif => "gif",
+            _ => "jpg",
+        }
+    }
+}
+
+/** 是否为 HEIC/HEIF/AVIF，image 无法解码这些格式，需启用 heic 特性由 libheif 解码 */
+pub fn is_heif(data: &[u8]) -> bool {
+    const BRANDS: [&[u8]; 9] = [
+        b"heic", b"heix", b"hevc", b"hevx", b"heim", b"heis", b"mif1", b"msf1", b"avif",
+    ];
+    data.len() >= 12 && &data[4..8] == b"ftyp" && BRANDS.contains(&&data[8..12])
+}
+
+/** EXIF 方向，没有 EXIF 时为 None */
+fn exif_orientation(data: &[u8]) -> Option<u32> {
+    let exif = exif::Reader::new()
+        .read_from_container(&mut Cursor::new(data))
+        .ok()?;
+    Some(
+        exif.get_field(exif::Tag::Orientation, exif::In::PRIMARY)
+            .and_then(|field| field.value.get_uint(0))
+            .unwrap_or(1),
+    )
+}
+
+/** 按 EXIF 方向旋转，去除 EXIF 后图片方向保持不变 */
+fn apply_orientation(img: DynamicImage, orientation: u32) -> DynamicImage {
+    match orientation {
+        2 => img.fliph(),
+        3 => img.rotate180(),
+        4 => img.flipv(),
+        5 => img.rotate90().fliph(),
+        6 => img.rotate90(),
+        7 => img.rotate270().fliph(),
+        8 => img.rotate270(),
+        _ => img,
+    }
+}
+
+fn detect(data: &[u8]) -> Result<ImageFormat, Box<dyn std::error::Error>> {
+    match image::guess_format(data) {
+        Ok(format) => Ok(format),
+        Err(e) => {
+            error!("无法识别图片格式: {}", e);
+            Err("无法识别的图片格式".into())
+        }
+    }
+}
+
+fn decode_error(e: image::ImageError) -> Box<dyn std::error::Error> {
+    error!("图片解码失败: {}", e);
+    "图片解码失败".into()
+}
+
+fn encode_error(e: image::ImageError) -> Box<dyn std::error::Error> {
+    error!("图片编码失败: {}", e);
+    "图片编码失败".into()
+}
+
+/** 缩放到最长边不超过 max */
+fn fit(img: DynamicImage, max: u32) -> DynamicImage {
+    if img.width() > max || img.height() > max {
+        img.resize(max, max, FilterType::Lanczos3)
+    } else {
+        img
+    }
+}
+
+fn encode(
+    img: &DynamicImage,
+    format: ImageFormat,
+    quality: u8,
+) -> Result<Vec<u8>, image::ImageError> {
+    let mut out = Vec::new();
+    if format == ImageFormat::Png {
+        img.write_to(&mut Cursor::new(&mut out), ImageOutputFormat::Png)?;
+    } else {
+        JpegEncoder::new_with_quality(&mut out, quality).encode_image(&img.to_rgb8())?;
+    }
+    Ok(out)
+}
+
+/**
+ * 预处理图片：非 JPEG/PNG/GIF 转换格式，超出尺寸或大小时缩小，并去除 EXIF
+ * 已满足要求的图片原样返回；GIF 保留动画
+ */
+pub fn prepare_image<'a>(
+    data: &'a [u8],
+    options: &ImageOptions,
+) -> Result<Processed<'a>, Box<dyn std::error::Error>> {
+    if is_heif(data) {
+        return prepare_heif(data, options);
+    }
+    let format = detect(data)?;
+    if format == ImageFormat::Gif {
+        return prepare_gif(data, options);
+    }
+    let orientation = exif_orientation(data);
+    let (width, height) = image::io::Reader::with_format(Cursor::new(data), format)
+        .into_dimensions()
+        .map_err(decode_error)?;
+    let supported = matches!(format, ImageFormat::Jpeg | ImageFormat::Png);
+    if supported
+        && orientation.is_none()
+        && data.len() <= options.max_bytes
+        && width.max(height) <= options.max_dimension
+    {
+        return Ok(Processed {
+            data: Cow::Borrowed(data),
+            format,
+        });
+    }
+
+    let img = image::load_from_memory_with_format(data, format).map_err(decode_error)?;
+    let img = apply_orientation(img, orientation.unwrap_or(1));
+    compress(img, options, &format!("{:?}", format), data.len())
+}
+
+/**
+ * 缩放后重新编码为 JPEG（有透明通道时为 PNG），超出大小时逐轮降低质量或缩小尺寸
+ * @param source:     原图格式，用于日志
+ * @param source_len: 原图大小，用于日志
+ */
+fn compress(
+    img: DynamicImage,
+    options: &ImageOptions,
+    source: &str,
+    source_len: usize,
+) -> Result<Processed<'static>, Box<dyn std::error::Error>> {
+    let (width, height) = (img.width(), img.height());
+    let mut img = fit(img, options.max_dimension);
+    let target = if img.color().has_alpha() {
+        ImageFormat::Png
+    } else {
+        ImageFormat::Jpeg
+    };
+    let mut quality = options.jpeg_quality;
+    let mut out = encode(&img, target, quality).map_err(encode_error)?;
+    for _ in 0..MAX_ROUNDS {
+        if out.len() <= options.max_bytes {
+            break;
+        }
+        if target == ImageFormat::Jpeg && quality > MIN_JPEG_QUALITY {
+            quality = quality.saturating_sub(10).max(MIN_JPEG_QUALITY);
+        } else {
+            let max = img.width().max(img.height()) * 3 / 4;
+            img = fit(img, max.max(1));
+        }
+        out = encode(&img, target, quality).map_err(encode_error)?;
+    }
+    if out.len() > options.max_bytes {
+        warn!(
+            "图片压缩后仍超过大小限制: {} > {}",
+            out.len(),
+            options.max_bytes
+        );
+    }
+    info!(
+        "图片预处理: {} {}x{} {}B -> {:?} {}x{} {}B",
+        source,
+        width,
+        height,
+        source_len,
+        target,
+        img.width(),
+        img.height(),
+        out.len()
+    );
+    Ok(Processed {
+        data: Cow::Owned(out),
+        format: target,
+    })
+}
+
+/** HEIC/HEIF 由 libheif 解码（已按 irot/imir 旋转），再按普通图片转换 */
+#[cfg(feature = "heic")]
+fn prepare_heif(
+    data: &[u8],
+    options: &ImageOptions,
+) -> Result<Processed<'static>, Box<dyn std::error::Error>> {
+    let img = decode_heif(data)?;
+    compress(img, options, "HEIF", data.len())
+}
+
+#[cfg(not(feature = "heic"))]
+fn prepare_heif(
+    _data: &[u8],
+    _options: &ImageOptions,
+) -> Result<Processed<'static>, Box<dyn std::error::Error>> {
+    error!("未启用 heic 特性，无法转换 HEIC/HEIF 图片");
+    Err("HEIC/HEIF 图片需要启用 heic 特性".into())
+}
+
+#[cfg(feature = "heic")]
+fn decode_heif(data: &[u8]) -> Result<DynamicImage, Box<dyn std::error::Error>> {
+    use image::{RgbImage, RgbaImage};
+    use libheif_rs::{ColorSpace, HeifContext, HeifError, LibHeif, RgbChroma};
+
+    let heif_error = |e: HeifError| -> Box<dyn std::error::Error> {
+        error!("HEIC/HEIF 解码失败: {}", e);
+        "图片解码失败".into()
+    };
+    let ctx = HeifContext::read_from_bytes(data).map_err(heif_error)?;
+    let handle = ctx.primary_image_handle().map_err(heif_error)?;
+    let alpha = handle.has_alpha_channel();
+    let (chroma, channels) = if alpha {
+        (RgbChroma::Rgba, 4)
+    } else {
+        (RgbChroma::Rgb, 3)
+    };
+    let image = LibHeif::new()
+        .decode(&handle, ColorSpace::Rgb(chroma), None)
+        .map_err(heif_error)?;
+    let plane = match image.planes().interleaved {
+        Some(plane) => plane,
+        None => {
+            error!("HEIC/HEIF 解码结果缺少像素数据");
+            return Err("图片解码失败".into());
+        }
+    };
+    // 每行末尾可能有对齐填充，按 stride 逐行拷贝
+    let row = plane.width as usize * channels;
+    let mut pixels = Vec::with_capacity(row * plane.height as usize);
+    for y in 0..plane.height as usize {
+        let start = y * plane.stride;
+        pixels.extend_from_slice(&plane.data[start..start + row]);
+    }
+    let img = if alpha {
+        RgbaImage::from_raw(plane.width, plane.height, pixels).map(DynamicImage::ImageRgba8)
+    } else {
+        RgbImage::from_raw(plane.width, plane.height, pixels).map(DynamicImage::ImageRgb8)
+    };
+    match img {
+        Some(img) => Ok(img),
+        None => {
+            error!("HEIC/HEIF 像素数据长度不符");
+            Err("图片解码失败".into())
+        }
+    }
+}
+
+/** 动图逐帧缩小，直到满足尺寸和大小限制 */
+fn prepare_gif<'a>(
+    data: &'a [u8],
+    options: &ImageOptions,
+) -> Result<Processed<'a>, Box<dyn std::error::Error>> {
+    let decoder = GifDecoder::new(Cursor::new(data)).map_err(decode_error)?;
+    let frames = decoder
+        .into_frames()
+        .collect_frames()
+        .map_err(decode_error)?;
+    let (width, height) = match frames.first() {
+        Some(frame) => frame.buffer().dimensions(),
+        None => return Err("GIF 没有任何帧".into()),
+    };
+    let longest = width.max(height);
+    if data.len() <= options.max_bytes && longest <= options.max_dimension {
+        return Ok(Processed {
+            data: Cow::Borrowed(data),
+            format: ImageFormat::Gif,
+        });
+    }
+
+    let mut max = longest.min(options.max_dimension);
+    let mut out = Vec::new();
+    for _ in 0..MAX_ROUNDS {
+        let scale = max as f64 / longest as f64;
+        let w = ((width as f64 * scale).round() as u32).max(1);
+        let h = ((height as f64 * scale).round() as u32).max(1);
+        let resized = frames.iter().map(|frame| {
+            let buffer = image::imageops::resize(frame.buffer(), w, h, FilterType::Triangle);
+            Frame::from_parts(buffer, 0, 0, frame.delay())
+        });
+        out.clear();
+        {
+            let mut encoder = GifEncoder::new_with_speed(&mut out, 10);
+            encoder.set_repeat(Repeat::Infinite).map_err(encode_error)?;
+            encoder.encode_frames(resized).map_err(encode_error)?;
+        }
+        if out.len() <= options.max_bytes {
+            break;
+        }
+        max = (max * 3 / 4).max(1);
+    }
+    if out.len() > options.max_bytes {
+        warn!(
+            "GIF 压缩后仍超过大小限制: {} > {}",
+            out.len(),
+            options.max_bytes
+        );
+    }
+    Ok(Processed {
+        data: Cow::Owned(out),
+        format: ImageFormat::Gif,
+    })
+}
+
+/**
+ * 预处理后暂存并发送
+ * Path 来源在本地读不到时（只有服务端可见的路径）不做处理直接发送
+ */
+fn send_processed<F>(
+    wechat: &mut WeChat,
+    media: Media,
+    options: &ImageOptions,
+    send: F,
+) -> Result<SendStatus, WcfError>
+where
+    F: FnOnce(&mut WeChat, &Path) -> Result<SendStatus, WcfError>,
+{
+    let (data, stem, original) = match media {
+        Media::Path(path) => match fs::read(&path) {
+            Ok(data) => {
+                let stem = path.file_stem().map(|s| s.to_string_lossy().to_string());
+                (data, stem, Some(path))
+            }
+            Err(e) => {
+                warn!("本地无法读取，跳过预处理: {}, {}", path.display(), e);
+                return send(wechat, &path);
+            }
+        },
+        media => {
+            let staged = media::stage(&wechat.dl_path, media, None)?;
+            match fs::read(staged.path()) {
+                Ok(data) => (data, None, None),
+                Err(e) => return Err(WcfError::Other(format!("读取暂存文件失败: {}", e))),
+            }
+        }
+    };
+    let processed = prepare_image(&data, options)?;
+    if let (false, Some(path)) = (processed.is_changed(), &original) {
+        return send(wechat, path);
+    }
+    let name = format!(
+        "{}.{}",
+        stem.as_deref().unwrap_or("image"),
+        processed.extension()
+    );
+    let path = media::stage_for_send(
+        wechat,
+        Media::from(processed.data.into_owned()),
+        Some(&name),
+    )?;
+    send(wechat, &path)
+}
+
+/** 预处理后发送图片，支持路径、字节、reader 和 URL */
+pub fn send_image<'a>(
+    wechat: &mut WeChat,
+    media: impl Into<Media<'a>>,
+    receiver: impl Into<Receiver>,
+    options: &ImageOptions,
+) -> Result<SendStatus, WcfError> {
+    let receiver = receiver.into();
+    send_processed(wechat, media.into(), options, |wechat, path| {
+        wechat::send_image(wechat, path.to_path_buf(), receiver)
+    })
+}
+
+/** 预处理后发送表情，动图超出限制时逐帧缩小 */
+pub fn send_emotion<'a>(
+    wechat: &mut WeChat,
+    media: impl Into<Media<'a>>,
+    receiver: impl Into<Receiver>,
+    options: &ImageOptions,
+) -> Result<SendStatus, WcfError> {
+    let receiver = receiver.into();
+    send_processed(wechat, media.into(), options, |wechat, path| {
+        wechat::send_emotion(wechat, path.to_path_buf(), receiver)
+    })
+}
+
+mod test {
+
+    #[test]
+    fn test_resize_and_convert() {
+        use std::io::Cursor;
+
+        use image::{DynamicImage, ImageFormat, ImageOutputFormat, RgbImage};
+
+        use crate::preprocess::{prepare_image, ImageOptions};
+
+        let img =
+            DynamicImage::ImageRgb8(RgbImage::from_pixel(300, 120, image::Rgb([200, 30, 30])));
+        let mut bmp = Vec::new();
+        img.write_to(&mut Cursor::new(&mut bmp), ImageOutputFormat::Bmp)
+            .unwrap();
+        let options = ImageOptions {
+            max_dimension: 100,
+            ..ImageOptions::image()
+        };
+        let processed = prepare_image(&bmp, &options).unwrap();
+        assert!(processed.is_changed());
+        assert_eq!(processed.format, ImageFormat::Jpeg);
+        let out = image::load_from_memory(&processed.data).unwrap();
+        assert_eq!((out.width(), out.height()), (100, 40));
+
+        let mut png = Vec::new();
+        img.write_to(&mut Cursor::new(&mut png), ImageOutputFormat::Png)
+            .unwrap();
+        let processed = prepare_image(&png, &ImageOptions::image()).unwrap();
+        assert!(!processed.is_changed());
+    }
+
+    #[test]
+    fn test_strip_exif() {
+        use image::{DynamicImage, ImageOutputFormat, RgbImage};
+
+        use crate::preprocess::{prepare_image, ImageOptions};
+
+        let img = DynamicImage::ImageRgb8(RgbImage::new(40, 20));
+        let mut jpeg = Vec::new();
+        img.write_to(
+            &mut std::io::Cursor::new(&mut jpeg),
+            ImageOutputFormat::Jpeg(90),
+        )
+        .unwrap();
+        // APP1: Exif，Orientation = 6（顺时针旋转 90 度）
+        let tiff: [u8; 26] = [
+            b'I', b'I', 42, 0, 8, 0, 0, 0, 1, 0, 0x12, 0x01, 3, 0, 1, 0, 0, 0, 6, 0, 0, 0, 0, 0, 0,
+            0,
+        ];
+        let mut app1 = vec![0xFF, 0xE1];
+        app1.extend_from_slice(&((2 + 6 + tiff.len()) as u16).to_be_bytes());
+        app1.extend_from_slice(b"Exif\0\0");
+        app1.extend_from_slice(&tiff);
+        let mut with_exif = jpeg[..2].to_vec();
+        with_exif.extend_from_slice(&app1);
+        with_exif.extend_from_slice(&jpeg[2..]);
+
+        let processed = prepare_image(&with_exif, &ImageOptions::image()).unwrap();
+        assert!(processed.is_changed());
+        assert!(!processed.data.windows(6).any(|w| w == b"Exif\0\0"));
+        let out = image::load_from_memory(&processed.data).unwrap();
+        assert_eq!((out.width(), out.height()), (20, 40));
+    }
+
+    #[test]
+    fn test_gif_and_heif() {
+        use std::time::Duration;
+
+        use image::{codecs::gif::GifEncoder, Delay, Frame, ImageFormat, RgbaImage};
+
+        use crate::preprocess::{is_heif, prepare_image, ImageOptions};
+
+        let mut gif = Vec::new();
+        {
+            let mut encoder = GifEncoder::new(&mut gif);
+            let frames = [[255, 0, 0, 255], [0, 0, 255, 255]].map(|color| {
+                Frame::from_parts(
+                    RgbaImage::from_pixel(200, 100, image::Rgba(color)),
+                    0,
+                    0,
+                    Delay::from_saturating_duration(Duration::from_millis(100)),
+                )
+            });
+            encoder.encode_frames(frames).unwrap();
+        }
+        let options = ImageOptions {
+            max_dimension: 50,
+            ..ImageOptions::emotion()
+        };
+        let processed = prepare_image(&gif, &options).unwrap();
+        assert_eq!(processed.format, ImageFormat::Gif);
+        let out = image::load_from_memory(&processed.data).unwrap();
+        assert_eq!((out.width(), out.height()), (50, 25));
+
+        // 只有文件头，无论是否启用 heic 特性都无法解码
+        let heic = b"\0\0\0\x18ftypheic\0\0\0\0mif1heic";
+        assert!(is_heif(heic));
+        assert!(prepare_image(heic, &options).is_err());
+    }
+
+    #[cfg(feature = "heic")]
+    #[test]
+    fn test_heic() {
+        use image::ImageFormat;
+        use libheif_rs::{
+            Channel, ColorSpace, CompressionFormat, HeifContext, Image, LibHeif, RgbChroma,
+        };
+
+        use crate::preprocess::{is_heif, prepare_image, ImageOptions};
+
+        let (width, height) = (64, 32);
+        let mut image = Image::new(width, height, ColorSpace::Rgb(RgbChroma::C444)).unwrap();
+        for channel in [Channel::R, Channel::G, Channel::B] {
+            image.create_plane(channel, width, height, 8).unwrap();
+        }
+        let planes = image.planes_mut();
+        for plane in [planes.r, planes.g, planes.b] {
+            plane.unwrap().data.fill(128);
+        }
+        let lib_heif = LibHeif::new();
+        let mut encoder = match lib_heif.encoder_for_format(CompressionFormat::Hevc) {
+            Ok(encoder) => encoder,
+            Err(e) => {
+                println!("libheif 没有 HEVC 编码器，跳过: {}", e);
+                return;
+            }
+        };
+        let mut context = HeifContext::new().unwrap();
+        context.encode_image(&image, &mut encoder, None).unwrap();
+        let heic = context.write_to_bytes().unwrap();
+        assert!(is_heif(&heic));
+
+        let processed = prepare_image(&heic, &ImageOptions::image()).unwrap();
+        assert_eq!(processed.format, ImageFormat::Jpeg);
+        let out = image::load_from_memory(&processed.data).unwrap();
+        assert_eq!((out.width(), out.height()), (width, height));
+    }
+
+    #[cfg(not(feature = "heic"))]
+    #[test]
+    fn test_heic_disabled() {
+        use crate::preprocess::{prepare_image, ImageOptions};
+
+        let heic = b"\0\0\0\x18ftypheic\0\0\0\0mif1heic";
+        let e = prepare_image(heic, &ImageOptions::image()).unwrap_err();
+        assert!(e.to_string().contains("heic"));
+    }
+
+    #[test]
+    fn test_send_image() {
+        let mut wechat = crate::wechat::WeChat::default();
+        let status = crate::preprocess::send_image(
+            &mut wechat,
+            "https://raw.githubusercontent.com/lich0821/WeChatFerry/master/assets/TEQuant.jpeg",
+            crate::receiver::SpecialAccount::FileHelper,
+            &crate::preprocess::ImageOptions::image(),
+        )
+        .unwrap();
+        println!("Status: {}", status);
+    }
+}
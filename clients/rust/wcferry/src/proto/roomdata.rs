@@ -0,0 +1,22 @@
+/// MicroMsg.db ChatRoom 表 RoomData 字段
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct RoomData {
+    #[prost(message, repeated, tag = "1")]
+    pub members: ::prost::alloc::vec::Vec<room_data::RoomMember>,
+}
+/// Nested message and enum types in `RoomData`.
+pub mod room_data {
+    #[allow(clippy::derive_partial_eq_without_eq)]
+    #[derive(Clone, PartialEq, ::prost::Message)]
+    pub struct RoomMember {
+        /// 成员 wxid
+        #[prost(string, tag = "1")]
+        pub wxid: ::prost::alloc::string::String,
+        /// 群昵称
+        #[prost(string, tag = "2")]
+        pub name: ::prost::alloc::string::String,
+        #[prost(int32, tag = "3")]
+        pub state: i32,
+    }
+}
@@ -0,0 +1,294 @@
+use std::{fmt, str::FromStr};
+
+/** @所有人 */
+const NOTIFY_ALL: &str = "notify@all";
+pub(crate) const CHATROOM_SUFFIX: &str = "@chatroom";
+pub(crate) const OFFICIAL_PREFIX: &str = "gh_";
+/** 企业微信联系人的 id 后缀（xxxxxxxx@openim） */
+const OPENIM_SUFFIX: &str = "@openim";
+
+fn is_id_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || c == '_' || c == '-'
+}
+
+/** 个人微信号 wxid（wxid_xxxxxxxxxxxxxx 或早期自定义的 id），也包括企业微信联系人（xxxxxxxx@openim） */
+#[derive(Clone, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Wxid(String);
+
+impl Wxid {
+    /** @所有人（必须是群主或者管理员才有权限） */
+    pub fn all() -> Wxid {
+        Wxid(String::from(NOTIFY_ALL))
+    }
+
+    pub fn is_all(&self) -> bool {
+        self.0 == NOTIFY_ALL
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl FromStr for Wxid {
+    type Err = Box<dyn std::error::Error>;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s == NOTIFY_ALL {
+            return Ok(Wxid::all());
+        }
+        let id = s.strip_suffix(OPENIM_SUFFIX).unwrap_or(s);
+        if id.is_empty() || !id.chars().all(is_id_char) {
+            return Err(format!("无效的 wxid: {}", s).into());
+        }
+        if s.starts_with(OFFICIAL_PREFIX) || SpecialAccount::from_name(s).is_some() {
+            return Err(format!("不是个人微信号: {}", s).into());
+        }
+        Ok(Wxid(s.to_string()))
+    }
+}
+
+/** 群 id（xxxxxxxxxx@chatroom） */
+#[derive(Clone, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct RoomId(String);
+
+impl RoomId {
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl FromStr for RoomId {
+    type Err = Box<dyn std::error::Error>;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.strip_suffix(CHATROOM_SUFFIX) {
+            Some(id) if !id.is_empty() && id.chars().all(is_id_char) => Ok(RoomId(s.to_string())),
+            _ => Err(format!("无效的群 id: {}", s).into()),
+        }
+    }
+}
+
+/** 公众号 id（gh_xxxxxxxx） */
+#[derive(Clone, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct OfficialId(String);
+
+impl OfficialId {
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl FromStr for OfficialId {
+    type Err = Box<dyn std::error::Error>;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.strip_prefix(OFFICIAL_PREFIX) {
+            Some(id) if !id.is_empty() && id.chars().all(is_id_char) => {
+                Ok(OfficialId(s.to_string()))
+            }
+            _ => Err(format!("无效的公众号 id: {}", s).into()),
+        }
+    }
+}
+
+/** 微信内置的特殊账号 */
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum SpecialAccount {
+    /** 文件传输助手 */
+    FileHelper,
+    /** 朋友推荐消息 */
+    FMessage,
+    /** 语音记事本 */
+    MediaNote,
+    /** 漂流瓶 */
+    FloatBottle,
+    /** 新闻 */
+    NewsApp,
+}
+
+impl SpecialAccount {
+    pub const ALL: [SpecialAccount; 5] = [
+        SpecialAccount::FileHelper,
+        SpecialAccount::FMessage,
+        SpecialAccount::MediaNote,
+        SpecialAccount::FloatBottle,
+        SpecialAccount::NewsApp,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SpecialAccount::FileHelper => "filehelper",
+            SpecialAccount::FMessage => "fmessage",
+            SpecialAccount::MediaNote => "medianote",
+            SpecialAccount::FloatBottle => "floatbottle",
+            SpecialAccount::NewsApp => "newsapp",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<SpecialAccount> {
+        SpecialAccount::ALL
+            .into_iter()
+            .find(|account| account.as_str() == name)
+    }
+}
+
+/** 消息接收人 */
+#[derive(Clone, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum Receiver {
+    User(Wxid),
+    Room(RoomId),
+    Official(OfficialId),
+    Special(SpecialAccount),
+}
+
+impl Receiver {
+    pub fn as_str(&self) -> &str {
+        match self {
+            Receiver::User(wxid) => wxid.as_str(),
+            Receiver::Room(roomid) => roomid.as_str(),
+            Receiver::Official(id) => id.as_str(),
+            Receiver::Special(account) => account.as_str(),
+        }
+    }
+
+    pub fn is_room(&self) -> bool {
+        matches!(self, Receiver::Room(_))
+    }
+}
+
+impl FromStr for Receiver {
+    type Err = Box<dyn std::error::Error>;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if let Some(account) = SpecialAccount::from_name(s) {
+            return Ok(Receiver::Special(account));
+        }
+        if s.ends_with(CHATROOM_SUFFIX) {
+            return Ok(Receiver::Room(s.parse()?));
+        }
+        if s.starts_with(OFFICIAL_PREFIX) {
+            return Ok(Receiver::Official(s.parse()?));
+        }
+        if s == NOTIFY_ALL {
+            return Err("notify@all 不能作为接收人".into());
+        }
+        Ok(Receiver::User(s.parse()?))
+    }
+}
+
+impl From<Wxid> for Receiver {
+    fn from(wxid: Wxid) -> Self {
+        Receiver::User(wxid)
+    }
+}
+
+impl From<RoomId> for Receiver {
+    fn from(roomid: RoomId) -> Self {
+        Receiver::Room(roomid)
+    }
+}
+
+impl From<OfficialId> for Receiver {
+    fn from(id: OfficialId) -> Self {
+        Receiver::Official(id)
+    }
+}
+
+impl From<SpecialAccount> for Receiver {
+    fn from(account: SpecialAccount) -> Self {
+        Receiver::Special(account)
+    }
+}
+
+impl From<&Receiver> for Receiver {
+    fn from(receiver: &Receiver) -> Self {
+        receiver.clone()
+    }
+}
+
+impl fmt::Display for Wxid {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl fmt::Display for RoomId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl fmt::Display for OfficialId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl fmt::Display for Receiver {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/** 拼接成 TextMsg.aters 需要的逗号分隔格式 */
+pub fn join_aters(aters: &[Wxid]) -> String {
+    aters
+        .iter()
+        .map(|wxid| wxid.as_str())
+        .collect::<Vec<&str>>()
+        .join(",")
+}
+
+mod test {
+
+    #[test]
+    fn test_parse_receiver() {
+        use crate::receiver::{Receiver, SpecialAccount};
+
+        let receiver: Receiver = "wxid_xxxxxxxxxxxxx1".parse().unwrap();
+        assert!(matches!(receiver, Receiver::User(_)));
+        let receiver: Receiver = "34476879773@chatroom".parse().unwrap();
+        assert!(receiver.is_room());
+        assert_eq!(receiver.to_string(), "34476879773@chatroom");
+        let receiver: Receiver = "gh_3dfda90e39d6".parse().unwrap();
+        assert!(matches!(receiver, Receiver::Official(_)));
+        let receiver: Receiver = "filehelper".parse().unwrap();
+        assert_eq!(receiver, Receiver::Special(SpecialAccount::FileHelper));
+
+        assert!("".parse::<Receiver>().is_err());
+        assert!("@chatroom".parse::<Receiver>().is_err());
+        assert!("wxid_a,wxid_b".parse::<Receiver>().is_err());
+        assert!("notify@all".parse::<Receiver>().is_err());
+        assert!("gh_".parse::<Receiver>().is_err());
+
+        let receiver: Receiver = "1688850000000000@openim".parse().unwrap();
+        assert!(matches!(receiver, Receiver::User(_)));
+    }
+
+    #[test]
+    fn test_parse_wxid() {
+        use crate::receiver::{RoomId, Wxid};
+
+        assert!("wxid_xxxxxxxxxxxxx1".parse::<Wxid>().is_ok());
+        assert!("zhangsan_88".parse::<Wxid>().is_ok());
+        assert!("notify@all".parse::<Wxid>().unwrap().is_all());
+        assert!("34476879773@chatroom".parse::<Wxid>().is_err());
+        assert!("gh_3dfda90e39d6".parse::<Wxid>().is_err());
+        assert!("filehelper".parse::<Wxid>().is_err());
+        assert!("wxid_a b".parse::<Wxid>().is_err());
+        assert!("wxid_a".parse::<RoomId>().is_err());
+        assert!("1688850000000000@openim".parse::<Wxid>().is_ok());
+        assert!("@openim".parse::<Wxid>().is_err());
+        assert!("a@openim@openim".parse::<Wxid>().is_err());
+
+        let aters = vec![
+            "wxid_xxxxxxxxxxxxx1".parse::<Wxid>().unwrap(),
+            "wxid_xxxxxxxxxxxxx2".parse::<Wxid>().unwrap(),
+        ];
+        assert_eq!(
+            crate::receiver::join_aters(&aters),
+            "wxid_xxxxxxxxxxxxx1,wxid_xxxxxxxxxxxxx2"
+        );
+    }
+}
@@ -0,0 +1,155 @@
+use log::error;
+
+use crate::{
+    error::WcfError,
+    message::MSG_TYPE_TEXT,
+    receiver::{Receiver, Wxid},
+    status::SendStatus,
+    text::TextBuilder,
+    wechat::{self, wcf, WeChat},
+    xmlmsg::QuoteReply,
+};
+
+/** 回复选项 */
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct ReplyOptions {
+    /** 群消息时 @ 原发送者 */
+    pub mention: bool,
+    /** 以引用消息（appmsg type 57）的形式回复，优先于 mention */
+    pub quote: bool,
+}
+
+/**
+ * 回复消息应发往的目标：群消息发回群里，私聊发给对方
+ * 自己发出的私聊消息 sender 是自己，此时取 roomid（对方 wxid）
+ */
+pub fn reply_target(msg: &wcf::WxMsg) -> Result<Receiver, WcfError> {
+    let target = if msg.is_group || (msg.is_self && !msg.roomid.is_empty()) {
+        &msg.roomid
+    } else {
+        &msg.sender
+    };
+    match target.parse() {
+        Ok(receiver) => Ok(receiver),
+        Err(e) => {
+            error!("无法确定回复对象: {}", e);
+            Err(WcfError::InvalidArgument(format!(
+                "无法确定回复对象: {}",
+                e
+            )))
+        }
+    }
+}
+
+/**
+ * 回复收到的消息
+ * @param msg:     收到的消息
+ * @param text:    回复内容
+ * @param options: 是否 @ 原发送者、是否引用原消息
+ */
+pub fn reply(
+    wechat: &mut WeChat,
+    msg: &wcf::WxMsg,
+    text: &str,
+    options: ReplyOptions,
+) -> Result<SendStatus, WcfError> {
+    let receiver = reply_target(msg)?;
+
+    if options.quote {
+        let display_name = match &receiver {
+            Receiver::Room(roomid) => wechat::get_chatroom_members(wechat, roomid)?
+                .remove(&msg.sender)
+                .unwrap_or_default(),
+            _ => String::new(),
+        };
+        let quote = QuoteReply {
+            text: text.to_string(),
+            msg_id: msg.id,
+            msg_type: msg.r#type,
+            sender: msg.sender.clone(),
+            roomid: if msg.is_group {
+                msg.roomid.clone()
+            } else {
+                String::new()
+            },
+            display_name,
+            content: if msg.r#type == MSG_TYPE_TEXT {
+                msg.content.clone()
+            } else {
+                String::new()
+            },
+        };
+        return quote.build().send(wechat, receiver);
+    }
+
+    let mut builder = TextBuilder::new();
+    if options.mention && receiver.is_room() && !msg.is_self {
+        let sender: Wxid = match msg.sender.parse() {
+            Ok(sender) => sender,
+            Err(e) => {
+                error!("无效的发送者: {}", e);
+                return Err(WcfError::InvalidArgument(format!("无效的发送者: {}", e)));
+            }
+        };
+        builder = builder.mention(sender).text(" ");
+    }
+    builder.text(text).send(wechat, receiver)
+}
+
+mod test {
+
+    #[test]
+    fn test_reply_target() {
+        use crate::receiver::Receiver;
+        use crate::wechat::wcf::WxMsg;
+
+        let group = WxMsg {
+            is_group: true,
+            roomid: String::from("34476879773@chatroom"),
+            sender: String::from("wxid_abc"),
+            ..Default::default()
+        };
+        assert!(crate::reply::reply_target(&group).unwrap().is_room());
+
+        let private = WxMsg {
+            roomid: String::from("wxid_abc"),
+            sender: String::from("wxid_abc"),
+            ..Default::default()
+        };
+        assert_eq!(
+            crate::reply::reply_target(&private).unwrap(),
+            "wxid_abc".parse::<Receiver>().unwrap()
+        );
+
+        let own = WxMsg {
+            is_self: true,
+            roomid: String::from("wxid_abc"),
+            sender: String::from("wxid_me"),
+            ..Default::default()
+        };
+        assert_eq!(
+            crate::reply::reply_target(&own).unwrap().to_string(),
+            "wxid_abc"
+        );
+    }
+
+    #[test]
+    fn test_reply() {
+        let mut wechat = crate::wechat::WeChat::default();
+        let msg = crate::wechat::wcf::WxMsg {
+            is_group: true,
+            id: 8541264785412365478,
+            r#type: 1,
+            roomid: String::from("34476879773@chatroom"),
+            sender: String::from("wxid_abc"),
+            content: String::from("明天开会"),
+            ..Default::default()
+        };
+        let options = crate::reply::ReplyOptions {
+            mention: true,
+            quote: false,
+        };
+        let status = crate::reply::reply(&mut wechat, &msg, "收到", options).unwrap();
+        println!("Success: {}", status);
+    }
+}
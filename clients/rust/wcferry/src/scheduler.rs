@@ -0,0 +1,402 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    str::FromStr,
+    sync::atomic::{AtomicBool, Ordering},
+    thread,
+    time::Duration,
+};
+
+use chrono::{DateTime, Local, TimeZone};
+use log::{error, info, warn};
+use serde::{Deserialize, Serialize};
+
+use crate::{outbox::Payload, receiver::Receiver, status::SendStatus, wechat::WeChat};
+
+/** 轮询间隔 */
+const POLL_INTERVAL: Duration = Duration::from_secs(1);
+/** 超过计划时间该时长仍未执行视为错过 */
+const DEFAULT_GRACE: Duration = Duration::from_secs(60);
+
+/** 执行计划 */
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Schedule {
+    /** 只执行一次，秒级时间戳 */
+    Once { at: i64 },
+    /** cron 表达式：秒 分 时 日 月 周 [年]，按本地时区计算 */
+    Cron { expr: String },
+}
+
+/** 进程停止期间错过的执行如何处理 */
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MissedRun {
+    /** 跳过，等待下一次 */
+    #[default]
+    Skip,
+    /** 补发一次，多次错过也只补发一次 */
+    RunOnce,
+}
+
+/** 定时任务 */
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Job {
+    pub id: u64,
+    pub receiver: String,
+    pub payload: Payload,
+    pub schedule: Schedule,
+    pub missed: MissedRun,
+    /** 下次执行时间，秒级时间戳；为空表示已结束 */
+    pub next_run: Option<i64>,
+    pub last_run: Option<i64>,
+    pub last_error: Option<String>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct Store {
+    next_id: u64,
+    jobs: Vec<Job>,
+}
+
+/**
+ * 定时发送，任务保存在本地文件中，重启后继续生效
+ * @example let mut scheduler = Scheduler::open("jobs.json")?; scheduler.add_cron(receiver, Payload::text("早上好", &[]), "0 0 9 * * Mon-Fri", MissedRun::Skip)?; scheduler.run(&mut wechat, &stop)?;
+ */
+#[derive(Debug)]
+pub struct Scheduler {
+    path: PathBuf,
+    store: Store,
+    grace: Duration,
+}
+
+/** schedule 在 now 之后的下一次执行时间 */
+fn next_after(schedule: &Schedule, now: &DateTime<Local>) -> Option<i64> {
+    match schedule {
+        Schedule::Once { at } => (*at > now.timestamp()).then_some(*at),
+        Schedule::Cron { expr } => cron::Schedule::from_str(expr)
+            .ok()?
+            .after(now)
+            .next()
+            .map(|t| t.timestamp()),
+    }
+}
+
+impl Scheduler {
+    /** 打开任务文件，不存在时新建 */
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, Box<dyn std::error::Error>> {
+        let path = path.as_ref().to_path_buf();
+        let store = match fs::read(&path) {
+            Ok(data) => match serde_json::from_slice(&data) {
+                Ok(store) => store,
+                Err(e) => {
+                    error!("定时任务解析失败: {}, {}", path.display(), e);
+                    return Err("打开定时任务失败".into());
+                }
+            },
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Store::default(),
+            Err(e) => {
+                error!("读取定时任务失败: {}, {}", path.display(), e);
+                return Err("打开定时任务失败".into());
+            }
+        };
+        Ok(Scheduler {
+            path,
+            store,
+            grace: DEFAULT_GRACE,
+        })
+    }
+
+    /** 超过计划时间多久视为错过，默认 60 秒 */
+    pub fn grace(mut self, grace: Duration) -> Self {
+        self.grace = grace;
+        self
+    }
+
+    /**
+     * 添加一次性任务
+     * @param at:     执行时间，早于当前时间时报错
+     * @param missed: 停机错过执行时间（超过 grace）时的处理方式
+     */
+    pub fn add_once<Tz: TimeZone>(
+        &mut self,
+        receiver: impl Into<Receiver>,
+        payload: Payload,
+        at: DateTime<Tz>,
+        missed: MissedRun,
+    ) -> Result<u64, Box<dyn std::error::Error>> {
+        let at = at.timestamp();
+        if at <= Local::now().timestamp() {
+            error!("执行时间已过: {}", at);
+            return Err("添加定时任务失败".into());
+        }
+        self.add(receiver.into(), payload, Schedule::Once { at }, missed)
+    }
+
+    /**
+     * 添加周期任务
+     * @param expr:   cron 表达式，如 "0 30 9 * * Mon-Fri" 为工作日 9:30
+     * @param missed: 错过执行时的处理方式
+     */
+    pub fn add_cron(
+        &mut self,
+        receiver: impl Into<Receiver>,
+        payload: Payload,
+        expr: &str,
+        missed: MissedRun,
+    ) -> Result<u64, Box<dyn std::error::Error>> {
+        if let Err(e) = cron::Schedule::from_str(expr) {
+            error!("cron 表达式无效: {}, {}", expr, e);
+            return Err("添加定时任务失败".into());
+        }
+        let schedule = Schedule::Cron {
+            expr: expr.to_string(),
+        };
+        self.add(receiver.into(), payload, schedule, missed)
+    }
+
+    fn add(
+        &mut self,
+        receiver: Receiver,
+        payload: Payload,
+        schedule: Schedule,
+        missed: MissedRun,
+    ) -> Result<u64, Box<dyn std::error::Error>> {
+        let next_run = next_after(&schedule, &Local::now());
+        if next_run.is_none() {
+            error!("定时任务不会再执行: {:?}", schedule);
+            return Err("添加定时任务失败".into());
+        }
+        self.store.next_id += 1;
+        let id = self.store.next_id;
+        self.store.jobs.push(Job {
+            id,
+            receiver: receiver.to_string(),
+            payload,
+            schedule,
+            missed,
+            next_run,
+            last_run: None,
+            last_error: None,
+        });
+        self.save()?;
+        Ok(id)
+    }
+
+    pub fn remove(&mut self, id: u64) -> Result<bool, Box<dyn std::error::Error>> {
+        let before = self.store.jobs.len();
+        self.store.jobs.retain(|job| job.id != id);
+        if self.store.jobs.len() == before {
+            return Ok(false);
+        }
+        self.save()?;
+        Ok(true)
+    }
+
+    pub fn get(&self, id: u64) -> Option<&Job> {
+        self.store.jobs.iter().find(|job| job.id == id)
+    }
+
+    pub fn jobs(&self) -> &[Job] {
+        &self.store.jobs
+    }
+
+    /**
+     * 找出到期的任务并推进下次执行时间
+     * @return 需要发送的任务 id
+     */
+    fn plan(&mut self, now: &DateTime<Local>) -> Vec<u64> {
+        let grace = self.grace.as_secs() as i64;
+        let mut due = vec![];
+        for job in self.store.jobs.iter_mut() {
+            let next = match job.next_run {
+                Some(next) if next <= now.timestamp() => next,
+                _ => continue,
+            };
+            job.next_run = next_after(&job.schedule, now);
+            if now.timestamp() - next > grace && job.missed == MissedRun::Skip {
+                warn!("定时任务 {} 错过执行时间，已跳过", job.id);
+                job.last_error = Some(String::from("错过执行时间"));
+                continue;
+            }
+            due.push(job.id);
+        }
+        due
+    }
+
+    /**
+     * 执行所有到期的任务
+     * @return 本次执行的任务数
+     */
+    pub fn tick(&mut self, wechat: &mut WeChat) -> Result<usize, Box<dyn std::error::Error>> {
+        let now = Local::now();
+        let expired = self
+            .store
+            .jobs
+            .iter()
+            .any(|job| job.next_run.is_some_and(|next| next <= now.timestamp()));
+        if !expired {
+            return Ok(0);
+        }
+        // 先保存推进后的执行时间（包括被跳过的任务），发送途中崩溃时重启后不会重复发送
+        let due = self.plan(&now);
+        self.save()?;
+        for id in due.iter() {
+            let job = match self.store.jobs.iter_mut().find(|job| job.id == *id) {
+                Some(job) => job,
+                None => continue,
+            };
+            info!("执行定时任务 {}: {}", job.id, job.receiver);
+            let result = match job.receiver.parse::<Receiver>() {
+                Ok(receiver) => job.payload.send(wechat, receiver),
+                Err(e) => {
+                    warn!("{}", e);
+                    Ok(SendStatus::ReceiverInvalid)
+                }
+            };
+            job.last_run = Some(now.timestamp());
+            job.last_error = match result {
+                Ok(SendStatus::Success) => None,
+                Ok(status) => Some(status.to_string()),
+                Err(e) => Some(e.to_string()),
+            };
+            if let Some(e) = &job.last_error {
+                warn!("定时任务 {} 发送失败: {}", job.id, e);
+            }
+            self.save()?;
+        }
+        Ok(due.len())
+    }
+
+    /** 循环执行，直到 stop 被置为 true */
+    pub fn run(
+        &mut self,
+        wechat: &mut WeChat,
+        stop: &AtomicBool,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        while !stop.load(Ordering::Relaxed) {
+            self.tick(wechat)?;
+            thread::sleep(POLL_INTERVAL);
+        }
+        Ok(())
+    }
+
+    fn save(&self) -> Result<(), Box<dyn std::error::Error>> {
+        let data = serde_json::to_vec_pretty(&self.store)?;
+        let tmp = self.path.with_extension("tmp");
+        if let Err(e) = fs::write(&tmp, data).and_then(|_| fs::rename(&tmp, &self.path)) {
+            error!("保存定时任务失败: {}, {}", self.path.display(), e);
+            return Err("保存定时任务失败".into());
+        }
+        Ok(())
+    }
+}
+
+mod test {
+
+    #[test]
+    fn test_plan() {
+        use chrono::{Duration, Local};
+
+        use crate::outbox::Payload;
+        use crate::receiver::SpecialAccount;
+        use crate::scheduler::{MissedRun, Scheduler};
+
+        let path = std::env::temp_dir().join(format!("wcferry-jobs-{}.json", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+
+        // 统一使用同一个时间点，避免测试途中跨过整分钟让 cron 任务提前到期
+        let now = Local::now();
+        let mut scheduler = Scheduler::open(&path).unwrap();
+        let once = scheduler
+            .add_once(
+                SpecialAccount::FileHelper,
+                Payload::text("once", &[]),
+                now + Duration::minutes(5),
+                MissedRun::RunOnce,
+            )
+            .unwrap();
+        let once_skip = scheduler
+            .add_once(
+                SpecialAccount::FileHelper,
+                Payload::text("once skip", &[]),
+                now + Duration::minutes(5),
+                MissedRun::Skip,
+            )
+            .unwrap();
+        let skip = scheduler
+            .add_cron(
+                SpecialAccount::FileHelper,
+                Payload::text("skip", &[]),
+                "0 * * * * *",
+                MissedRun::Skip,
+            )
+            .unwrap();
+        let catch_up = scheduler
+            .add_cron(
+                SpecialAccount::FileHelper,
+                Payload::text("catch up", &[]),
+                "0 * * * * *",
+                MissedRun::RunOnce,
+            )
+            .unwrap();
+        assert!(scheduler
+            .add_cron(
+                SpecialAccount::FileHelper,
+                Payload::text("bad", &[]),
+                "every minute",
+                MissedRun::Skip,
+            )
+            .is_err());
+        assert!(scheduler
+            .add_once(
+                SpecialAccount::FileHelper,
+                Payload::text("past", &[]),
+                now - Duration::minutes(1),
+                MissedRun::RunOnce,
+            )
+            .is_err());
+
+        // 重启后任务仍在
+        let mut scheduler = Scheduler::open(&path).unwrap();
+        assert_eq!(scheduler.jobs().len(), 4);
+        assert!(scheduler.plan(&now).is_empty());
+
+        // 停机 1 小时后：Skip 跳过，RunOnce 只补发一次
+        let later = now + Duration::hours(1);
+        let due = scheduler.plan(&later);
+        assert_eq!(due, vec![once, catch_up]);
+        assert!(scheduler.get(once).unwrap().next_run.is_none());
+        assert!(scheduler.get(once_skip).unwrap().next_run.is_none());
+        assert!(scheduler.get(once_skip).unwrap().last_error.is_some());
+        assert!(scheduler.get(skip).unwrap().last_error.is_some());
+        assert!(scheduler.get(skip).unwrap().next_run.unwrap() > later.timestamp());
+        assert!(scheduler.plan(&later).is_empty());
+
+        assert!(scheduler.remove(skip).unwrap());
+        assert!(!scheduler.remove(skip).unwrap());
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[test]
+    fn test_tick() {
+        use crate::outbox::Payload;
+        use crate::receiver::SpecialAccount;
+        use crate::scheduler::{MissedRun, Scheduler};
+
+        let mut wechat = crate::wechat::WeChat::default();
+        let path = std::env::temp_dir().join("wcferry-jobs-tick.json");
+        let mut scheduler = Scheduler::open(&path).unwrap();
+        scheduler
+            .add_cron(
+                SpecialAccount::FileHelper,
+                Payload::text("Hello, wcferry!", &[]),
+                "* * * * * *",
+                MissedRun::Skip,
+            )
+            .unwrap();
+        std::thread::sleep(std::time::Duration::from_secs(1));
+        let count = scheduler.tick(&mut wechat).unwrap();
+        println!("Executed: {}", count);
+    }
+}
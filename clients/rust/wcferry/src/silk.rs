@@ -0,0 +1,167 @@
+use std::{fs, path::Path};
+
+use log::error;
+
+// 语音消息（type 34）解码
+// 微信语音是 SILK v3 编码，文件头为 `\x02#!SILK_V3`（比标准 SILK 多一个 0x02 字节），
+// 解码输出 16 bit 单声道小端 PCM，可再封装为 WAV
+
+const SILK_HEADER: &[u8] = b"#!SILK_V3";
+const AMR_HEADER: &[u8] = b"#!AMR";
+
+/**
+ * SILK 解码器支持的输出采样率
+ * 不含 44100：silk-rs 按每毫秒整数个采样分配输出缓冲，44.1 kHz 下一帧会越界
+ */
+pub const SAMPLE_RATES: [u32; 6] = [8000, 12000, 16000, 24000, 32000, 48000];
+/** 微信语音的原始采样率 */
+pub const DEFAULT_SAMPLE_RATE: u32 = 24000;
+
+/** 是否为 SILK 数据（兼容微信的 0x02 前缀） */
+pub fn is_silk(data: &[u8]) -> bool {
+    strip_header_prefix(data).starts_with(SILK_HEADER)
+}
+
+/** 去掉微信在标准 SILK 文件头前添加的 0x02 */
+fn strip_header_prefix(data: &[u8]) -> &[u8] {
+    match data.first() {
+        Some(0x02) => &data[1..],
+        _ => data,
+    }
+}
+
+/**
+ * 解码为 PCM
+ * @param data:        SILK 数据，可带或不带微信的 0x02 前缀
+ * @param sample_rate: 输出采样率，取值见 SAMPLE_RATES
+ * @return 16 bit 单声道小端 PCM
+ */
+pub fn decode_pcm(data: &[u8], sample_rate: u32) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+    if !SAMPLE_RATES.contains(&sample_rate) {
+        error!("不支持的采样率: {}", sample_rate);
+        return Err("语音解码失败".into());
+    }
+    let data = strip_header_prefix(data);
+    if !data.starts_with(SILK_HEADER) {
+        if data.starts_with(AMR_HEADER) {
+            error!("AMR 格式语音暂不支持");
+        } else {
+            error!("不是 SILK 格式数据");
+        }
+        return Err("语音解码失败".into());
+    }
+    match silk_rs::decode_silk(data, sample_rate as i32) {
+        Ok(pcm) => Ok(pcm),
+        Err(e) => {
+            error!("SILK 解码失败: {:?}", e);
+            Err("语音解码失败".into())
+        }
+    }
+}
+
+/** 解码为 WAV */
+pub fn decode_wav(data: &[u8], sample_rate: u32) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+    let pcm = decode_pcm(data, sample_rate)?;
+    Ok(pcm_to_wav(&pcm, sample_rate))
+}
+
+/**
+ * 解码语音文件并写入 WAV 文件
+ * @param src:         SILK 文件路径
+ * @param dst:         WAV 文件路径
+ * @param sample_rate: 输出采样率，取值见 SAMPLE_RATES
+ */
+pub fn decode_file<P: AsRef<Path>, Q: AsRef<Path>>(
+    src: P,
+    dst: Q,
+    sample_rate: u32,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let data = match fs::read(src.as_ref()) {
+        Ok(data) => data,
+        Err(e) => {
+            error!("读取文件失败: {}, {}", src.as_ref().display(), e);
+            return Err("语音解码失败".into());
+        }
+    };
+    let wav = decode_wav(&data, sample_rate)?;
+    match fs::write(dst.as_ref(), wav) {
+        Ok(()) => Ok(()),
+        Err(e) => {
+            error!("写入文件失败: {}, {}", dst.as_ref().display(), e);
+            Err("语音解码失败".into())
+        }
+    }
+}
+
+/** 为 16 bit 单声道 PCM 加上 WAV 文件头 */
+pub fn pcm_to_wav(pcm: &[u8], sample_rate: u32) -> Vec<u8> {
+    let channels: u16 = 1;
+    let bits_per_sample: u16 = 16;
+    let block_align = channels * bits_per_sample / 8;
+    let byte_rate = sample_rate * block_align as u32;
+    let data_len = pcm.len() as u32;
+
+    let mut wav = Vec::with_capacity(44 + pcm.len());
+    wav.extend_from_slice(b"RIFF");
+    wav.extend_from_slice(&(36 + data_len).to_le_bytes());
+    wav.extend_from_slice(b"WAVE");
+    wav.extend_from_slice(b"fmt ");
+    wav.extend_from_slice(&16u32.to_le_bytes());
+    wav.extend_from_slice(&1u16.to_le_bytes()); // PCM
+    wav.extend_from_slice(&channels.to_le_bytes());
+    wav.extend_from_slice(&sample_rate.to_le_bytes());
+    wav.extend_from_slice(&byte_rate.to_le_bytes());
+    wav.extend_from_slice(&block_align.to_le_bytes());
+    wav.extend_from_slice(&bits_per_sample.to_le_bytes());
+    wav.extend_from_slice(b"data");
+    wav.extend_from_slice(&data_len.to_le_bytes());
+    wav.extend_from_slice(pcm);
+    wav
+}
+
+mod test {
+
+    #[test]
+    fn test_is_silk() {
+        assert!(crate::silk::is_silk(b"\x02#!SILK_V3\x0c\x00"));
+        assert!(crate::silk::is_silk(b"#!SILK_V3\x0c\x00"));
+        assert!(!crate::silk::is_silk(b"#!AMR\n"));
+        assert!(!crate::silk::is_silk(b""));
+    }
+
+    #[test]
+    fn test_decode_invalid() {
+        assert!(crate::silk::decode_pcm(b"#!AMR\n", 24000).is_err());
+        assert!(crate::silk::decode_pcm(b"\x02#!SILK_V3", 22050).is_err());
+        assert!(crate::silk::decode_pcm(b"\x02#!SILK_V3", 44100).is_err());
+    }
+
+    #[test]
+    fn test_decode_fixture() {
+        // 100 ms 的 440 Hz 正弦波，24 kHz 编码，带微信的 0x02 前缀
+        let data = include_bytes!("../tests/fixtures/voice.silk");
+        assert!(crate::silk::is_silk(data));
+        for rate in crate::silk::SAMPLE_RATES {
+            let pcm = crate::silk::decode_pcm(data, rate).unwrap();
+            assert_eq!(pcm.len(), (rate / 10 * 2) as usize);
+        }
+        let wav = crate::silk::decode_wav(data, 16000).unwrap();
+        assert_eq!(&wav[24..28], &16000u32.to_le_bytes());
+        assert_eq!(wav.len(), 44 + 3200);
+    }
+
+    #[test]
+    fn test_pcm_to_wav() {
+        let pcm = [0x01u8, 0x00, 0xFF, 0x7F];
+        let wav = crate::silk::pcm_to_wav(&pcm, 24000);
+        assert_eq!(wav.len(), 44 + pcm.len());
+        assert_eq!(&wav[0..4], b"RIFF");
+        assert_eq!(&wav[4..8], &40u32.to_le_bytes());
+        assert_eq!(&wav[8..16], b"WAVEfmt ");
+        assert_eq!(&wav[24..28], &24000u32.to_le_bytes());
+        assert_eq!(&wav[28..32], &48000u32.to_le_bytes());
+        assert_eq!(&wav[36..40], b"data");
+        assert_eq!(&wav[40..44], &4u32.to_le_bytes());
+        assert_eq!(&wav[44..], &pcm);
+    }
+}
@@ -0,0 +1,84 @@
+use std::fmt;
+
+// 服务端返回的状态码：
+// send_* 成功为 0，接收人为空等失败为 -1
+// 同意加好友、群加人/踢人、解密图片、接收转账返回 1 表示成功，0 表示失败
+// 刷新朋友圈原样返回微信内部函数的结果，1 表示成功
+
+/** send_* 接口成功时的状态码 */
+pub const STATUS_SEND_OK: i32 = 0;
+/** 加好友、群成员、转账、解密、朋友圈等接口成功时的状态码 */
+pub const STATUS_OK: i32 = 1;
+
+/** 操作状态 */
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SendStatus {
+    Success,
+    /** 发送前在客户端检查出文件不存在，服务端不会返回该状态 */
+    FileNotFound,
+    /** 发送失败后在客户端查询出微信未登录，服务端不会返回该状态 */
+    NotLoggedIn,
+    /** 客户端检查出接收人为空或格式错误，未发给服务端 */
+    ReceiverInvalid,
+    /** 服务端返回的失败状态码 */
+    Unknown(i32),
+}
+
+impl SendStatus {
+    /**
+     * 解析服务端状态码
+     * @param status:  Response.status
+     * @param success: 该接口表示成功的状态码，send_* 为 0，其余为 1
+     */
+    pub fn from_code(status: i32, success: i32) -> Self {
+        match status {
+            s if s == success => SendStatus::Success,
+            s => SendStatus::Unknown(s),
+        }
+    }
+
+    pub fn is_success(&self) -> bool {
+        *self == SendStatus::Success
+    }
+}
+
+impl fmt::Display for SendStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SendStatus::Success => write!(f, "成功"),
+            SendStatus::FileNotFound => write!(f, "文件不存在"),
+            SendStatus::NotLoggedIn => write!(f, "微信未登录"),
+            SendStatus::ReceiverInvalid => write!(f, "接收人无效"),
+            SendStatus::Unknown(status) => write!(f, "未知状态: {}", status),
+        }
+    }
+}
+
+mod test {
+
+    #[test]
+    fn test_from_code() {
+        use crate::status::{SendStatus, STATUS_OK, STATUS_SEND_OK};
+
+        assert_eq!(
+            SendStatus::from_code(0, STATUS_SEND_OK),
+            SendStatus::Success
+        );
+        assert_eq!(
+            SendStatus::from_code(1, STATUS_SEND_OK),
+            SendStatus::Unknown(1)
+        );
+        assert_eq!(SendStatus::from_code(1, STATUS_OK), SendStatus::Success);
+        assert_eq!(SendStatus::from_code(0, STATUS_OK), SendStatus::Unknown(0));
+        assert_eq!(
+            SendStatus::from_code(-1, STATUS_OK),
+            SendStatus::Unknown(-1)
+        );
+        assert_eq!(
+            SendStatus::from_code(-1, STATUS_SEND_OK),
+            SendStatus::Unknown(-1)
+        );
+        assert_eq!(SendStatus::ReceiverInvalid.to_string(), "接收人无效");
+        assert!(!SendStatus::ReceiverInvalid.is_success());
+    }
+}
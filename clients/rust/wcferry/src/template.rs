@@ -0,0 +1,329 @@
+use std::collections::HashMap;
+
+use log::{error, warn};
+
+use crate::{
+    error::WcfError,
+    receiver::Receiver,
+    status::SendStatus,
+    wechat::{self, wcf, WeChat},
+};
+
+// 发送文本模板
+// {name}                  变量，先取调用方提供的变量，再取接收人的联系人信息
+// {#if city}...{#else}...{/if}  变量非空时输出前一部分，否则输出 {#else} 之后的部分；{#if !city} 取反
+// {{ 和 }}                输出字面的 { 和 }
+
+/** 来自接收人 RpcContact 的变量 */
+const CONTACT_VARS: [&str; 9] = [
+    "name", "nickname", "remark", "wxid", "code", "country", "province", "city", "gender",
+];
+
+/** 替换进消息的变量值中的 @ 改为全角，避免被当作 @ 他人 */
+const ESCAPED_AT: char = '＠';
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum Node {
+    Text(String),
+    Var(String),
+    If {
+        var: String,
+        negate: bool,
+        then: Vec<Node>,
+        otherwise: Vec<Node>,
+    },
+}
+
+/** 解析时尚未闭合的 {#if} */
+struct OpenIf {
+    var: String,
+    negate: bool,
+    then: Option<Vec<Node>>,
+}
+
+/**
+ * 编译后的消息模板，编译一次后可按接收人多次渲染
+ * @example let template = Template::compile("{name}，你好{#if city}，{city}天气不错{/if}")?.var("date", "周一");
+ */
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Template {
+    nodes: Vec<Node>,
+    vars: HashMap<String, String>,
+}
+
+fn is_var_name(name: &str) -> bool {
+    !name.is_empty()
+        && name
+            .chars()
+            .all(|c| c.is_alphanumeric() || c == '_' || c == '-')
+}
+
+fn parse_error(msg: &str, source: &str) -> Box<dyn std::error::Error> {
+    error!("模板解析失败: {}, {}", msg, source);
+    format!("模板解析失败: {}", msg).into()
+}
+
+impl Template {
+    /** 解析模板 */
+    pub fn compile(source: &str) -> Result<Template, Box<dyn std::error::Error>> {
+        let mut stack: Vec<(OpenIf, Vec<Node>)> = vec![];
+        let mut nodes: Vec<Node> = vec![];
+        let mut text = String::new();
+        let mut rest = source;
+        while let Some(i) = rest.find(['{', '}']) {
+            text.push_str(&rest[..i]);
+            let after = &rest[i + 1..];
+            if rest[i..].starts_with("{{") {
+                text.push('{');
+                rest = &rest[i + 2..];
+                continue;
+            }
+            if rest[i..].starts_with("}}") {
+                text.push('}');
+                rest = &rest[i + 2..];
+                continue;
+            }
+            if rest[i..].starts_with('}') {
+                return Err(parse_error("多余的 }", source));
+            }
+            let end = match after.find('}') {
+                Some(end) => end,
+                None => return Err(parse_error("缺少 }", source)),
+            };
+            let tag = after[..end].trim();
+            rest = &after[end + 1..];
+            if !text.is_empty() {
+                nodes.push(Node::Text(std::mem::take(&mut text)));
+            }
+
+            if let Some(cond) = tag.strip_prefix("#if ") {
+                let cond = cond.trim();
+                let (negate, var) = match cond.strip_prefix('!') {
+                    Some(var) => (true, var.trim()),
+                    None => (false, cond),
+                };
+                if !is_var_name(var) {
+                    return Err(parse_error(&format!("无效的条件 {}", cond), source));
+                }
+                let open = OpenIf {
+                    var: var.to_string(),
+                    negate,
+                    then: None,
+                };
+                stack.push((open, std::mem::take(&mut nodes)));
+            } else if tag == "#else" {
+                match stack.last_mut() {
+                    Some((open, _)) if open.then.is_none() => {
+                        open.then = Some(std::mem::take(&mut nodes));
+                    }
+                    _ => return Err(parse_error("多余的 {#else}", source)),
+                }
+            } else if tag == "/if" {
+                let (open, parent) = match stack.pop() {
+                    Some(open) => open,
+                    None => return Err(parse_error("多余的 {/if}", source)),
+                };
+                let body = std::mem::replace(&mut nodes, parent);
+                let (then, otherwise) = match open.then {
+                    Some(then) => (then, body),
+                    None => (body, vec![]),
+                };
+                nodes.push(Node::If {
+                    var: open.var,
+                    negate: open.negate,
+                    then,
+                    otherwise,
+                });
+            } else if is_var_name(tag) {
+                nodes.push(Node::Var(tag.to_string()));
+            } else {
+                return Err(parse_error(&format!("无效的标签 {{{}}}", tag), source));
+            }
+        }
+        if !stack.is_empty() {
+            return Err(parse_error("缺少 {/if}", source));
+        }
+        text.push_str(rest);
+        if !text.is_empty() {
+            nodes.push(Node::Text(text));
+        }
+        Ok(Template {
+            nodes,
+            vars: HashMap::new(),
+        })
+    }
+
+    /** 调用方提供的变量，优先于联系人信息 */
+    pub fn var(mut self, name: &str, value: &str) -> Self {
+        self.vars.insert(name.to_string(), value.to_string());
+        self
+    }
+
+    /** 是否用到联系人信息，决定发送前是否需要查询联系人 */
+    pub fn needs_contact(&self) -> bool {
+        fn uses(nodes: &[Node], vars: &HashMap<String, String>) -> bool {
+            nodes.iter().any(|node| match node {
+                Node::Text(_) => false,
+                Node::Var(var) => !vars.contains_key(var) && CONTACT_VARS.contains(&var.as_str()),
+                Node::If {
+                    var,
+                    then,
+                    otherwise,
+                    ..
+                } => {
+                    (!vars.contains_key(var) && CONTACT_VARS.contains(&var.as_str()))
+                        || uses(then, vars)
+                        || uses(otherwise, vars)
+                }
+            })
+        }
+        uses(&self.nodes, &self.vars)
+    }
+
+    fn lookup<'a>(
+        &'a self,
+        var: &str,
+        wxid: &'a str,
+        contact: Option<&'a wcf::RpcContact>,
+    ) -> &'a str {
+        if let Some(value) = self.vars.get(var) {
+            return value;
+        }
+        let field = |f: fn(&wcf::RpcContact) -> &str| contact.map(f).unwrap_or_default();
+        match var {
+            "name" => [field(|c| &c.remark), field(|c| &c.name), wxid]
+                .into_iter()
+                .find(|s| !s.is_empty())
+                .unwrap_or_default(),
+            "nickname" => field(|c| &c.name),
+            "remark" => field(|c| &c.remark),
+            "wxid" => wxid,
+            "code" => field(|c| &c.code),
+            "country" => field(|c| &c.country),
+            "province" => field(|c| &c.province),
+            "city" => field(|c| &c.city),
+            "gender" => match contact.map(|c| c.gender) {
+                Some(1) => "男",
+                Some(2) => "女",
+                _ => "",
+            },
+            _ => {
+                warn!("模板变量未定义: {}", var);
+                ""
+            }
+        }
+    }
+
+    fn render_nodes(
+        &self,
+        nodes: &[Node],
+        wxid: &str,
+        contact: Option<&wcf::RpcContact>,
+        out: &mut String,
+    ) {
+        for node in nodes {
+            match node {
+                Node::Text(text) => out.push_str(text),
+                Node::Var(var) => {
+                    let value = self.lookup(var, wxid, contact);
+                    out.extend(value.chars().map(|c| if c == '@' { ESCAPED_AT } else { c }));
+                }
+                Node::If {
+                    var,
+                    negate,
+                    then,
+                    otherwise,
+                } => {
+                    let present = !self.lookup(var, wxid, contact).trim().is_empty();
+                    let branch = if present != *negate { then } else { otherwise };
+                    self.render_nodes(branch, wxid, contact, out);
+                }
+            }
+        }
+    }
+
+    /**
+     * 按接收人渲染
+     * @param wxid:    接收人 wxid
+     * @param contact: 接收人的联系人信息，不在联系人中时为 None
+     */
+    pub fn render(&self, wxid: &str, contact: Option<&wcf::RpcContact>) -> String {
+        let mut out = String::new();
+        self.render_nodes(&self.nodes, wxid, contact, &mut out);
+        out
+    }
+}
+
+/**
+ * 按接收人渲染模板后通过 send_text 发送
+ * 模板用到联系人信息时会先查询联系人列表，批量发送请使用 broadcast 的 Content::Template
+ */
+pub fn send_template(
+    wechat: &mut WeChat,
+    template: &Template,
+    receiver: impl Into<Receiver>,
+) -> Result<SendStatus, WcfError> {
+    let receiver = receiver.into();
+    let contact = if template.needs_contact() {
+        wechat::get_contacts(wechat)?
+            .unwrap_or_default()
+            .contacts
+            .into_iter()
+            .find(|c| c.wxid == receiver.as_str())
+    } else {
+        None
+    };
+    let msg = template.render(receiver.as_str(), contact.as_ref());
+    wechat::send_text(wechat, msg, receiver, &[])
+}
+
+mod test {
+
+    #[test]
+    fn test_render() {
+        use crate::template::Template;
+        use crate::wechat::wcf::RpcContact;
+
+        let contact = RpcContact {
+            wxid: String::from("wxid_abc"),
+            name: String::from("张三@home"),
+            remark: String::new(),
+            city: String::from("Shenzhen"),
+            gender: 1,
+            ..Default::default()
+        };
+        let template = Template::compile(
+            "{name}，{date}好{#if city}，{city}天气不错{#else}！{/if}{#if !remark}（{gender}）{/if} {{x}}",
+        )
+        .unwrap()
+        .var("date", "周一");
+        assert!(template.needs_contact());
+        assert_eq!(
+            template.render("wxid_abc", Some(&contact)),
+            "张三＠home，周一好，Shenzhen天气不错（男） {x}"
+        );
+        assert_eq!(
+            template.render("wxid_abc", None),
+            "wxid_abc，周一好！（） {x}"
+        );
+
+        let template = Template::compile("{#if date}今天是{date}{/if}")
+            .unwrap()
+            .var("date", "周一");
+        assert!(!template.needs_contact());
+        assert_eq!(template.render("wxid_abc", None), "今天是周一");
+    }
+
+    #[test]
+    fn test_compile_error() {
+        use crate::template::Template;
+
+        assert!(Template::compile("{name").is_err());
+        assert!(Template::compile("name}").is_err());
+        assert!(Template::compile("{#if city}").is_err());
+        assert!(Template::compile("{/if}").is_err());
+        assert!(Template::compile("{#if city}{#else}{#else}{/if}").is_err());
+        assert!(Template::compile("{a b}").is_err());
+        assert!(Template::compile("{#if a}{#if b}x{/if}{/if}").is_ok());
+    }
+}
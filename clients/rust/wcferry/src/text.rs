@@ -0,0 +1,599 @@
+use std::{collections::HashMap, ops::Range, thread, time::Duration};
+
+use log::{error, warn};
+
+use crate::{
+    emoji::{self, Lang},
+    error::WcfError,
+    filter::WordFilter,
+    receiver::{Receiver, Wxid},
+    status::SendStatus,
+    wechat::{self, WeChat},
+};
+
+/** 微信客户端在 @昵称 之后插入的分隔符（四分之一空格） */
+const MENTION_SEPARATOR: char = '\u{2005}';
+
+/** @ 在 msg 中的字节范围及对应的 wxid */
+type Mentions = Vec<(Range<usize>, Wxid)>;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum Segment {
+    Text(String),
+    Mention(Wxid),
+}
+
+/**
+ * 带 @ 的文本消息
+ * @example TextBuilder::new().text("hi ").mention(wxid).text(" see above").mention_all()
+ */
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct TextBuilder {
+    segments: Vec<Segment>,
+    shortcodes: bool,
+}
+
+impl TextBuilder {
+    pub fn new() -> Self {
+        TextBuilder::default()
+    }
+
+    pub fn text(mut self, text: &str) -> Self {
+        self.segments.push(Segment::Text(text.to_string()));
+        self
+    }
+
+    pub fn mention(mut self, wxid: Wxid) -> Self {
+        self.segments.push(Segment::Mention(wxid));
+        self
+    }
+
+    /** @所有人（必须是群主或者管理员才有权限） */
+    pub fn mention_all(self) -> Self {
+        self.mention(Wxid::all())
+    }
+
+    /** 发送时将文字中的 Unicode emoji 转为 [微笑] 等微信表情代码 */
+    pub fn shortcodes(mut self, shortcodes: bool) -> Self {
+        self.shortcodes = shortcodes;
+        self
+    }
+
+    pub fn has_mentions(&self) -> bool {
+        self.segments
+            .iter()
+            .any(|segment| matches!(segment, Segment::Mention(_)))
+    }
+
+    /**
+     * 生成 send_text 需要的 msg 和 aters
+     * @param receiver: 消息接收人，有 @ 时必须是群
+     * @param names:    wxid -> 群昵称，见 get_chatroom_members
+     */
+    pub fn build(
+        &self,
+        receiver: &Receiver,
+        names: &HashMap<String, String>,
+    ) -> Result<(String, Vec<Wxid>), Box<dyn std::error::Error>> {
+        let (msg, mentions) = self.render(receiver, names)?;
+        let mut aters: Vec<Wxid> = vec![];
+        for (_, wxid) in mentions {
+            if !aters.contains(&wxid) {
+                aters.push(wxid);
+            }
+        }
+        Ok((msg, aters))
+    }
+
+    /** 生成 msg，并记录每个 @ 在 msg 中的字节范围 */
+    fn render(
+        &self,
+        receiver: &Receiver,
+        names: &HashMap<String, String>,
+    ) -> Result<(String, Mentions), Box<dyn std::error::Error>> {
+        if self.has_mentions() && !receiver.is_room() {
+            error!("私聊不能 @: {}", receiver);
+            return Err("私聊不能 @".into());
+        }
+        let mut msg = String::new();
+        let mut mentions = vec![];
+        for segment in &self.segments {
+            match segment {
+                Segment::Text(text) if self.shortcodes => {
+                    msg.push_str(&emoji::to_shortcode(text, Lang::Zh))
+                }
+                Segment::Text(text) => msg.push_str(text),
+                Segment::Mention(wxid) => {
+                    let name = if wxid.is_all() {
+                        "所有人"
+                    } else {
+                        match names.get(wxid.as_str()) {
+                            Some(name) if !name.is_empty() => name.as_str(),
+                            _ => {
+                                warn!("{} 不在群 {} 中", wxid, receiver);
+                                wxid.as_str()
+                            }
+                        }
+                    };
+                    let start = msg.len();
+                    msg.push('@');
+                    msg.push_str(name);
+                    msg.push(MENTION_SEPARATOR);
+                    mentions.push((start..msg.len(), wxid.clone()));
+                }
+            }
+        }
+        Ok((msg, mentions))
+    }
+
+    /** 查询群昵称后通过 send_text 发送 */
+    pub fn send(
+        &self,
+        wechat: &mut WeChat,
+        receiver: impl Into<Receiver>,
+    ) -> Result<SendStatus, WcfError> {
+        let receiver = receiver.into();
+        let names = match &receiver {
+            Receiver::Room(roomid) if self.has_mentions() => {
+                wechat::get_chatroom_members(wechat, roomid)?
+            }
+            _ => HashMap::default(),
+        };
+        let (msg, aters) = self
+            .build(&receiver, &names)
+            .map_err(|e| WcfError::InvalidArgument(e.to_string()))?;
+        wechat::send_text(wechat, msg, receiver, &aters)
+    }
+
+    /** 查询群昵称后通过 send_long_text 分段发送，每段只 @ 该段中出现的人 */
+    pub fn send_long(
+        &self,
+        wechat: &mut WeChat,
+        receiver: impl Into<Receiver>,
+        options: &LongTextOptions,
+    ) -> Result<Vec<SendStatus>, WcfError> {
+        let receiver = receiver.into();
+        let names = match &receiver {
+            Receiver::Room(roomid) if self.has_mentions() => {
+                wechat::get_chatroom_members(wechat, roomid)?
+            }
+            _ => HashMap::default(),
+        };
+        let (msg, mentions) = self
+            .render(&receiver, &names)
+            .map_err(|e| WcfError::InvalidArgument(e.to_string()))?;
+        let (msg, mentions) = filter_rendered(wechat.word_filter.as_deref(), msg, mentions)?;
+        let protected: Vec<Range<usize>> = mentions.iter().map(|(r, _)| r.clone()).collect();
+        let chunks = split_with(&msg, options, &protected, &mentions);
+        send_chunks(wechat, chunks, &receiver, options)
+    }
+}
+
+/** 默认每段的最大字节数（UTF-8） */
+pub const DEFAULT_MAX_BYTES: usize = 2048;
+/** 默认每段之间的发送间隔 */
+pub const DEFAULT_CHUNK_DELAY: Duration = Duration::from_millis(500);
+
+/** 长文本分段选项 */
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct LongTextOptions {
+    /** 每段的最大字节数（UTF-8），包含编号 */
+    pub max_bytes: usize,
+    /** 多于一段时在每段开头加上 (1/3) 这样的编号 */
+    pub numbered: bool,
+    /** 每段之间的发送间隔 */
+    pub delay: Duration,
+}
+
+impl Default for LongTextOptions {
+    fn default() -> Self {
+        LongTextOptions {
+            max_bytes: DEFAULT_MAX_BYTES,
+            numbered: false,
+            delay: DEFAULT_CHUNK_DELAY,
+        }
+    }
+}
+
+impl LongTextOptions {
+    pub fn max_bytes(mut self, max_bytes: usize) -> Self {
+        self.max_bytes = max_bytes;
+        self
+    }
+
+    pub fn numbered(mut self, numbered: bool) -> Self {
+        self.numbered = numbered;
+        self
+    }
+
+    pub fn delay(mut self, delay: Duration) -> Self {
+        self.delay = delay;
+        self
+    }
+}
+
+/** 依次尝试的切分位置：段落、行、句子、词 */
+const BREAKS: [&[&str]; 4] = [
+    &["\n\n"],
+    &["\n"],
+    &["。", "！", "？", "；", "…", "!", "?", ";", ". "],
+    &["，", "、", ",", " ", "\t"],
+];
+
+/**
+ * 找出文本中 @昵称 + 分隔符 的字节范围
+ * 昵称中不含换行和 @，找不到分隔符的 @ 不视为提及
+ */
+fn find_mentions(text: &str) -> Vec<Range<usize>> {
+    let mut mentions = vec![];
+    let mut from = 0;
+    while let Some(offset) = text[from..].find('@') {
+        let start = from + offset;
+        let rest = &text[start + 1..];
+        let end = rest
+            .find([MENTION_SEPARATOR, '\n', '@'])
+            .filter(|&i| rest[i..].starts_with(MENTION_SEPARATOR));
+        match end {
+            Some(i) => {
+                let end = start + 1 + i + MENTION_SEPARATOR.len_utf8();
+                mentions.push(start..end);
+                from = end;
+            }
+            None => from = start + 1,
+        }
+    }
+    mentions
+}
+
+/** 在 text 的前 max_bytes 字节内找切分位置，返回值总是大于 0 */
+fn cut_point(text: &str, max_bytes: usize, protected: &[Range<usize>]) -> usize {
+    let mut limit = max_bytes.min(text.len());
+    while !text.is_char_boundary(limit) {
+        limit -= 1;
+    }
+    let allowed = |i: usize| i > 0 && !protected.iter().any(|r| r.start < i && i < r.end);
+    let window = &text[..limit];
+    for seps in BREAKS {
+        let best = seps
+            .iter()
+            .flat_map(|sep| window.rmatch_indices(sep).map(|(i, s)| i + s.len()))
+            .filter(|&i| allowed(i))
+            .max();
+        if let Some(i) = best {
+            return i;
+        }
+    }
+    // 没有合适的标点时在字符边界硬切，但不切开 @
+    if let Some(i) = (1..=limit)
+        .rev()
+        .find(|&i| text.is_char_boundary(i) && allowed(i))
+    {
+        return i;
+    }
+    // 单个 @ 或字符已超过上限，只能整体放入
+    protected
+        .iter()
+        .find(|r| r.start == 0)
+        .map(|r| r.end)
+        .unwrap_or_else(|| text.chars().next().map_or(text.len(), char::len_utf8))
+}
+
+/** 按上限切分，返回各段在 text 中的字节范围，段首尾的换行会被去掉 */
+fn split_ranges(text: &str, max_bytes: usize, protected: &[Range<usize>]) -> Vec<Range<usize>> {
+    let max_bytes = max_bytes.max(1);
+    let mut ranges = vec![];
+    let mut start = 0;
+    while start < text.len() {
+        let rest = &text[start..];
+        let trimmed = rest.trim_start_matches('\n');
+        start += rest.len() - trimmed.len();
+        if start >= text.len() {
+            break;
+        }
+        let local: Vec<Range<usize>> = protected
+            .iter()
+            .filter(|r| r.end > start)
+            .map(|r| r.start.saturating_sub(start)..r.end - start)
+            .collect();
+        let cut = if text.len() - start <= max_bytes {
+            text.len() - start
+        } else {
+            cut_point(&text[start..], max_bytes, &local)
+        };
+        let end = start + text[start..start + cut].trim_end_matches('\n').len();
+        if end > start {
+            ranges.push(start..end);
+        }
+        start += cut;
+    }
+    ranges
+}
+
+fn number_prefix(index: usize, total: usize) -> String {
+    format!("({}/{}) ", index, total)
+}
+
+/**
+ * 切分并按需编号，每段附带其中出现的 @
+ * @param protected: 不能切开的范围
+ * @param mentions:  @ 的范围及对应的 wxid
+ */
+fn split_with(
+    text: &str,
+    options: &LongTextOptions,
+    protected: &[Range<usize>],
+    mentions: &[(Range<usize>, Wxid)],
+) -> Vec<(String, Vec<Wxid>)> {
+    let mut ranges = split_ranges(text, options.max_bytes, protected);
+    if options.numbered && ranges.len() > 1 {
+        // 编号占用的长度取决于总段数，重新切分直到段数稳定
+        loop {
+            let reserved = number_prefix(ranges.len(), ranges.len()).len();
+            let max_bytes = options.max_bytes.saturating_sub(reserved);
+            let resplit = split_ranges(text, max_bytes, protected);
+            if resplit.len() == ranges.len() {
+                ranges = resplit;
+                break;
+            }
+            ranges = resplit;
+        }
+    }
+    let total = ranges.len();
+    ranges
+        .into_iter()
+        .enumerate()
+        .map(|(index, range)| {
+            let mut aters: Vec<Wxid> = vec![];
+            for (r, wxid) in mentions {
+                if range.start <= r.start && r.end <= range.end && !aters.contains(wxid) {
+                    aters.push(wxid.clone());
+                }
+            }
+            let chunk = &text[range];
+            let chunk = if options.numbered && total > 1 {
+                number_prefix(index + 1, total) + chunk
+            } else {
+                chunk.to_string()
+            };
+            (chunk, aters)
+        })
+        .collect()
+}
+
+/**
+ * 按段落、行、句子切分长文本，不会切开多字节字符和 @
+ * @param max_bytes: 每段的最大字节数（UTF-8）
+ * @return 各段文本
+ */
+pub fn split_text(text: &str, max_bytes: usize) -> Vec<String> {
+    split_ranges(text, max_bytes, &find_mentions(text))
+        .into_iter()
+        .map(|range| text[range].to_string())
+        .collect()
+}
+
+/**
+ * 分段前过滤整条消息，避免敏感词被切到两段中
+ * 遮盖是逐字替换，字符位置不变，按字符位置换算 @ 的范围
+ */
+fn filter_rendered(
+    filter: Option<&WordFilter>,
+    msg: String,
+    mentions: Mentions,
+) -> Result<(String, Mentions), WcfError> {
+    let filtered = wechat::filter_text(filter, msg.clone())?;
+    if filtered == msg {
+        return Ok((msg, mentions));
+    }
+    let remap = |pos: usize| {
+        let chars = msg[..pos].chars().count();
+        filtered
+            .char_indices()
+            .nth(chars)
+            .map_or(filtered.len(), |(i, _)| i)
+    };
+    let mentions = mentions
+        .into_iter()
+        .map(|(range, wxid)| (remap(range.start)..remap(range.end), wxid))
+        .collect();
+    Ok((filtered, mentions))
+}
+
+/** 各段已在分段前整体过滤过，不再逐段过滤 */
+fn send_chunks(
+    wechat: &mut WeChat,
+    chunks: Vec<(String, Vec<Wxid>)>,
+    receiver: &Receiver,
+    options: &LongTextOptions,
+) -> Result<Vec<SendStatus>, WcfError> {
+    let mut statuses = vec![];
+    for (index, (chunk, aters)) in chunks.into_iter().enumerate() {
+        if index > 0 && !options.delay.is_zero() {
+            thread::sleep(options.delay);
+        }
+        let status = wechat::send_filtered_text(wechat, chunk, receiver, &aters)?;
+        let success = status.is_success();
+        statuses.push(status);
+        if !success {
+            error!("长文本第 {} 段发送失败: {}", index + 1, statuses[index]);
+            break;
+        }
+    }
+    Ok(statuses)
+}
+
+/**
+ * 分段发送长文本
+ * 先对整条消息做敏感词过滤再分段，按顺序发送，某段失败后不再发送后续各段
+ * @param msg:      要发送的消息，@ 的写法同 send_text
+ * @param receiver: 消息接收人
+ * @param aters:    要 @ 的 wxid，按顺序对应 msg 中的 @昵称，每段只 @ 该段中出现的人
+ * @param options:  分段选项
+ * @return 已发送各段的状态
+ */
+pub fn send_long_text(
+    wechat: &mut WeChat,
+    msg: String,
+    receiver: impl Into<Receiver>,
+    aters: &[Wxid],
+    options: &LongTextOptions,
+) -> Result<Vec<SendStatus>, WcfError> {
+    let receiver = receiver.into();
+    let msg = wechat::filter_text(wechat.word_filter.as_deref(), msg)?;
+    let spans = find_mentions(&msg);
+    let mentions: Mentions = if spans.len() == aters.len() {
+        spans.iter().cloned().zip(aters.iter().cloned()).collect()
+    } else {
+        if !aters.is_empty() {
+            warn!("@ 数量与 aters 不一致，所有 aters 将随第一段发送");
+        }
+        vec![]
+    };
+    let mut chunks = split_with(&msg, options, &spans, &mentions);
+    if mentions.is_empty() && !aters.is_empty() {
+        if let Some((_, first)) = chunks.first_mut() {
+            *first = aters.to_vec();
+        }
+    }
+    send_chunks(wechat, chunks, &receiver, options)
+}
+
+mod test {
+
+    #[test]
+    fn test_build() {
+        use std::collections::HashMap;
+
+        use crate::receiver::{Receiver, Wxid};
+        use crate::text::TextBuilder;
+
+        let zhangsan: Wxid = "wxid_zhangsan".parse().unwrap();
+        let lisi: Wxid = "wxid_lisi".parse().unwrap();
+        let names = HashMap::from([
+            (String::from("wxid_zhangsan"), String::from("张三")),
+            (String::from("wxid_lisi"), String::new()),
+        ]);
+        let room: Receiver = "34476879773@chatroom".parse().unwrap();
+
+        let (msg, aters) = TextBuilder::new()
+            .text("hi ")
+            .mention(zhangsan.clone())
+            .text(" see above ")
+            .mention(lisi.clone())
+            .mention(zhangsan.clone())
+            .mention_all()
+            .build(&room, &names)
+            .unwrap();
+        assert_eq!(
+            msg,
+            "hi @张三\u{2005} see above @wxid_lisi\u{2005}@张三\u{2005}@所有人\u{2005}"
+        );
+        assert_eq!(aters, vec![zhangsan.clone(), lisi, Wxid::all()]);
+
+        let private: Receiver = "wxid_zhangsan".parse().unwrap();
+        assert!(TextBuilder::new()
+            .mention_all()
+            .build(&private, &names)
+            .is_err());
+        let (msg, aters) = TextBuilder::new()
+            .text("hello")
+            .build(&private, &names)
+            .unwrap();
+        assert_eq!(msg, "hello");
+        assert!(aters.is_empty());
+
+        let (msg, _) = TextBuilder::new()
+            .shortcodes(true)
+            .text("好的👌")
+            .build(&private, &names)
+            .unwrap();
+        assert_eq!(msg, "好的[OK]");
+    }
+
+    #[test]
+    fn test_split_text() {
+        use crate::text::split_text;
+
+        assert_eq!(split_text("short", 100), vec!["short"]);
+        assert_eq!(
+            split_text("第一段。\n\n第二段，还有一句。", 30),
+            vec!["第一段。", "第二段，还有一句。"]
+        );
+        assert_eq!(
+            split_text("line one\nline two\nline three", 20),
+            vec!["line one\nline two", "line three"]
+        );
+        assert_eq!(
+            split_text("句子一。句子二。句子三。", 24),
+            vec!["句子一。句子二。", "句子三。"]
+        );
+        // 没有标点时按字符边界切，不会切开多字节字符
+        let chunks = split_text("一二三四五六七八九十", 10);
+        assert_eq!(chunks, vec!["一二三", "四五六", "七八九", "十"]);
+        // 不切开 @
+        let chunks = split_text("ab@张三\u{2005}cd", 6);
+        assert_eq!(chunks, vec!["ab", "@张三\u{2005}", "cd"]);
+    }
+
+    #[test]
+    fn test_split_with() {
+        use crate::receiver::Wxid;
+        use crate::text::{find_mentions, split_with, LongTextOptions};
+
+        let zhangsan: Wxid = "wxid_zhangsan".parse().unwrap();
+        let lisi: Wxid = "wxid_lisi".parse().unwrap();
+        let text = "@张三\u{2005}早上好。\n@李四\u{2005}晚上好。";
+        let spans = find_mentions(text);
+        assert_eq!(spans.len(), 2);
+        let mentions: Vec<_> = spans
+            .iter()
+            .cloned()
+            .zip([zhangsan.clone(), lisi.clone()])
+            .collect();
+        let options = LongTextOptions::default().max_bytes(30).numbered(true);
+        let chunks = split_with(text, &options, &spans, &mentions);
+        assert_eq!(
+            chunks,
+            vec![
+                (String::from("(1/2) @张三\u{2005}早上好。"), vec![zhangsan]),
+                (String::from("(2/2) @李四\u{2005}晚上好。"), vec![lisi]),
+            ]
+        );
+        assert!(chunks.iter().all(|(chunk, _)| chunk.len() <= 30));
+
+        let chunks = split_with("hello", &options, &[], &[]);
+        assert_eq!(chunks, vec![(String::from("hello"), vec![])]);
+    }
+
+    #[test]
+    fn test_filter_rendered() {
+        use crate::filter::WordFilter;
+        use crate::receiver::Wxid;
+        use crate::text::filter_rendered;
+
+        let filter = WordFilter::parse("mask 傻瓜\nblock 赌博").unwrap();
+        let lisi: Wxid = "wxid_lisi".parse().unwrap();
+        let msg = String::from("傻瓜 @李四\u{2005}你好");
+        let (msg, mentions) =
+            filter_rendered(Some(&filter), msg, vec![(7..17, lisi.clone())]).unwrap();
+        assert_eq!(msg, "** @李四\u{2005}你好");
+        assert_eq!(&msg[mentions[0].0.clone()], "@李四\u{2005}");
+        assert!(filter_rendered(Some(&filter), String::from("赌博"), vec![]).is_err());
+    }
+
+    #[test]
+    fn test_send_long_text() {
+        let mut wechat = crate::wechat::WeChat::default();
+        let msg = "Hello, wcferry!\n".repeat(300);
+        let options = crate::text::LongTextOptions::default().numbered(true);
+        let statuses = crate::text::send_long_text(
+            &mut wechat,
+            msg,
+            crate::receiver::SpecialAccount::FileHelper,
+            &[],
+            &options,
+        )
+        .unwrap();
+        println!("Statuses: {:?}", statuses);
+    }
+}
@@ -1,10 +1,24 @@
-use std::{env, path::PathBuf, process::Command, time::Duration, vec};
+use std::{
+    env,
+    path::{Path, PathBuf},
+    process::Command,
+    sync::Arc,
+    time::Duration,
+    vec,
+};
 
 use log::{error, info, warn};
 use nng::options::{Options, RecvTimeout};
 use prost::Message;
 use std::collections::HashMap;
 
+use crate::error::WcfError;
+use crate::filter::WordFilter;
+use crate::limiter::{MsgKind, RateLimiter};
+use crate::path_mapper::PathMapper;
+use crate::receiver::{join_aters, Receiver, RoomId, Wxid};
+use crate::status::{SendStatus, STATUS_OK, STATUS_SEND_OK};
+
 const DEFAULT_URL: &'static str = "tcp://127.0.0.1:10086";
 const LISTEN_URL: &'static str = "tcp://127.0.0.1:10087";
 
@@ -12,6 +26,10 @@ pub mod wcf {
     include!("proto/wcf.rs");
 }
 
+pub mod roomdata {
+    include!("proto/roomdata.rs");
+}
+
 #[derive(Clone, Debug)]
 pub struct WeChat {
     pub url: String,
@@ -20,6 +38,14 @@ pub struct WeChat {
     pub socket: nng::Socket,
     pub listening: bool,
     pub enable_accept_firend: bool,
+    /** 网络资源、字节等待发送内容的暂存目录 */
+    pub dl_path: PathBuf,
+    /** 客户端与服务端不在同一台机器时的路径映射 */
+    pub path_mapper: Option<PathMapper>,
+    /** 发送限流，多个 WeChat 克隆共享同一个限流器 */
+    pub rate_limiter: Option<Arc<RateLimiter>>,
+    /** 发送前的敏感词过滤，作用于 send_text 的文本和 send_xml 的文本节点 */
+    pub word_filter: Option<Arc<WordFilter>>,
 }
 
 #[derive(Clone, Debug)]
@@ -48,6 +74,10 @@ impl WeChat {
             socket,
             listening: false,
             enable_accept_firend: false,
+            dl_path: env::temp_dir().join("wcferry"),
+            path_mapper: None,
+            rate_limiter: None,
+            word_filter: None,
         }
     }
 }
@@ -89,6 +119,47 @@ pub fn stop(wechat: &mut WeChat) -> Result<(), Box<dyn std::error::Error>> {
     Ok(())
 }
 
+/** 客户端路径转为服务端路径，配置了 path_mapper 时按规则映射，没有匹配的规则视为无效路径 */
+fn remote_path(wechat: &WeChat, path: &Path) -> Result<String, WcfError> {
+    if let Some(mapper) = &wechat.path_mapper {
+        if !mapper.maps_local(path) {
+            error!("path_mapper 中没有匹配的规则: {}", path.display());
+            return Err(WcfError::InvalidArgument(format!("路径未映射: {}", path.display())));
+        }
+        return Ok(mapper.to_remote(path));
+    }
+    match path.to_str() {
+        Some(path) => Ok(String::from(path)),
+        None => {
+            error!("路径不是有效的 UTF-8: {}", path.display());
+            Err(WcfError::InvalidArgument(format!("路径无效: {}", path.display())))
+        }
+    }
+}
+
+/**
+ * 发送前在客户端检查文件，服务端的 send_* 只返回 0 或 -1，无法区分失败原因
+ * @param path: 要发送的文件，仅在配置了 path_mapper（即为客户端路径）时检查是否存在
+ * @return 文件不存在时返回 FileNotFound，可以发送时返回 None
+ */
+fn precheck(wechat: &WeChat, path: &Path) -> Option<SendStatus> {
+    if wechat.path_mapper.is_some() && !path.is_file() {
+        warn!("文件不存在: {}", path.display());
+        return Some(SendStatus::FileNotFound);
+    }
+    None
+}
+
+/** 发送前按 rate_limiter 限流，未配置时直接放行 */
+fn throttle(wechat: &WeChat, receiver: &Receiver, kind: MsgKind) -> Result<(), WcfError> {
+    match &wechat.rate_limiter {
+        Some(limiter) => limiter
+            .acquire(receiver.as_str(), kind)
+            .map_err(|_| WcfError::RateLimited),
+        None => Ok(()),
+    }
+}
+
 fn connect(url: &str) -> Result<nng::Socket, Box<dyn std::error::Error>> {
     let client = match nng::Socket::new(nng::Protocol::Pair1) {
         Ok(client) => client,
@@ -121,16 +192,21 @@ fn connect(url: &str) -> Result<nng::Socket, Box<dyn std::error::Error>> {
     Ok(client)
 }
 
+/**
+ * 发送请求并等待响应
+ * 请求未发出时返回 Transport，可以重试；已发出但未收到有效响应时服务端可能已经执行，
+ * 接收失败返回 Timeout，响应无法解析返回 Protocol，都不应直接重试
+ */
 fn send_cmd(
     wechat: &WeChat,
     req: wcf::Request,
-) -> Result<Option<wcf::response::Msg>, Box<dyn std::error::Error>> {
+) -> Result<Option<wcf::response::Msg>, WcfError> {
     let mut buf = Vec::with_capacity(req.encoded_len());
     match req.encode(&mut buf) {
         Ok(()) => (),
         Err(e) => {
             error!("序列化失败: {}", e);
-            return Err("通信失败".into());
+            return Err(WcfError::Protocol(String::from("请求序列化失败")));
         }
     };
     let msg = nng::Message::from(&buf[..]);
@@ -138,14 +214,14 @@ fn send_cmd(
         Ok(()) => {}
         Err(e) => {
             error!("Socket发送失败: {:?}, {}", e.0, e.1);
-            return Err("通信失败".into());
+            return Err(WcfError::Transport(String::from("请求发送失败")));
         }
     };
     let mut msg = match wechat.socket.recv() {
         Ok(msg) => msg,
         Err(e) => {
             error!("Socket接收失败: {}", e);
-            return Err("通信失败".into());
+            return Err(WcfError::Timeout(String::from("未收到响应，请求可能已执行")));
         }
     };
     // 反序列化为prost消息
@@ -153,13 +229,68 @@ fn send_cmd(
         Ok(res) => res,
         Err(e) => {
             error!("反序列化失败: {}", e);
-            return Err("通信失败".into());
+            return Err(WcfError::Protocol(String::from("响应反序列化失败")));
         }
     };
     msg.clear();
     Ok(response.msg)
 }
 
+/**
+ * 执行修改类请求并解析服务端状态码
+ * @param success: 该接口成功时的状态码
+ * @param action:  用于日志和错误信息，如 "微信消息发送"
+ */
+fn exec_status(
+    wechat: &WeChat,
+    req: wcf::Request,
+    success: i32,
+    action: &str,
+) -> Result<SendStatus, WcfError> {
+    let response = match send_cmd(wechat, req) {
+        Ok(res) => res,
+        Err(e) => {
+            error!("{}失败: {}", action, e);
+            return Err(e);
+        }
+    };
+    match response {
+        Some(wcf::response::Msg::Status(status)) => {
+            let status = SendStatus::from_code(status, success);
+            if !status.is_success() {
+                warn!("{}失败: {}", action, status);
+            }
+            Ok(status)
+        }
+        other => {
+            error!("{}响应无效: {:?}", action, other);
+            Err(WcfError::Protocol(format!("{}失败", action)))
+        }
+    }
+}
+
+/**
+ * 执行 send_* 请求，失败时再查询登录状态，未登录时返回 NotLoggedIn
+ * 只在失败后查询，成功发送不多一次 is_login 调用
+ */
+fn exec_send(wechat: &WeChat, req: wcf::Request, action: &str) -> Result<SendStatus, WcfError> {
+    let status = exec_status(wechat, req, STATUS_SEND_OK, action)?;
+    if status != SendStatus::Unknown(-1) {
+        return Ok(status);
+    }
+    match is_login(wechat) {
+        Ok(false) => {
+            warn!("微信未登录: {}失败", action);
+            Ok(SendStatus::NotLoggedIn)
+        }
+        Ok(true) => Ok(status),
+        Err(e) => {
+            error!("查询登录状态失败: {}", e);
+            Ok(status)
+        }
+    }
+}
+
 pub fn is_login(wechat: &WeChat) -> Result<bool, Box<dyn std::error::Error>> {
     let req = wcf::Request {
         func: wcf::Functions::FuncIsLogin.into(),
@@ -227,11 +358,15 @@ pub fn get_user_info(wechat: &mut WeChat) -> Result<Option<UserInfo>, Box<dyn st
     }
     match response.unwrap() {
         wcf::response::Msg::Ui(user_info) => {
+            let mut home = user_info.home;
+            if let Some(mapper) = &wechat.path_mapper {
+                mapper.map_incoming(&mut home);
+            }
             return Ok(Some(UserInfo {
                 wxid: user_info.wxid,
                 name: user_info.name,
                 mobile: user_info.mobile,
-                home: user_info.home,
+                home,
             }));
         }
         _ => {
@@ -353,189 +488,232 @@ pub fn exec_db_query(
 }
 
 /**
- * @param msg:      消息内容（如果是 @ 消息则需要有跟 @ 的人数量相同的 @）
+ * 获取群成员
+ * @param roomid: 群 id
+ * @return wxid -> 群昵称（未设置群昵称时为微信昵称）
+ */
+pub fn get_chatroom_members(
+    wechat: &mut WeChat,
+    roomid: &RoomId,
+) -> Result<HashMap<String, String>, Box<dyn std::error::Error>> {
+    let rows = match exec_db_query(
+        wechat,
+        String::from("MicroMsg.db"),
+        format!(
+            "SELECT RoomData FROM ChatRoom WHERE ChatRoomName = '{}';",
+            roomid
+        ),
+    ) {
+        Ok(rows) => rows,
+        Err(e) => {
+            error!("查询群信息失败: {}", e);
+            return Err("获取群成员失败".into());
+        }
+    };
+    let room_data = match rows.first().and_then(|row| row.fields.first()) {
+        Some(field) => field.content.as_slice(),
+        None => {
+            warn!("群不存在: {}", roomid);
+            return Ok(HashMap::default());
+        }
+    };
+    let room_data = match roomdata::RoomData::decode(room_data) {
+        Ok(room_data) => room_data,
+        Err(e) => {
+            error!("RoomData 反序列化失败: {}", e);
+            return Err("获取群成员失败".into());
+        }
+    };
+
+    let mut members: HashMap<String, String> = room_data
+        .members
+        .into_iter()
+        .map(|member| (member.wxid, member.name))
+        .collect();
+    let unnamed: Vec<String> = members
+        .iter()
+        .filter(|(_, name)| name.is_empty())
+        .map(|(wxid, _)| format!("'{}'", wxid.replace('\'', "''")))
+        .collect();
+    if unnamed.is_empty() {
+        return Ok(members);
+    }
+    let rows = exec_db_query(
+        wechat,
+        String::from("MicroMsg.db"),
+        format!(
+            "SELECT UserName, NickName FROM Contact WHERE UserName IN ({});",
+            unnamed.join(",")
+        ),
+    )?;
+    for row in rows {
+        if let [wxid, name] = &row.fields[..] {
+            members.insert(
+                String::from_utf8_lossy(&wxid.content).to_string(),
+                String::from_utf8_lossy(&name.content).to_string(),
+            );
+        }
+    }
+    Ok(members)
+}
+
+/**
+ * @param msg:      消息内容（如果是 @ 消息则需要有跟 @ 的人数量相同的 @，建议用
+ *                  TextBuilder 生成）
  * @param receiver: 消息接收人，私聊为 wxid（wxid_xxxxxxxxxxxxxx），群聊为
  *                  roomid（xxxxxxxxxx@chatroom）
- * @param aters:    群聊时要 @ 的人（私聊时为空字符串），多个用逗号分隔。@所有人 用
- *                  notify@all（必须是群主或者管理员才有权限）
+ * @param aters:    群聊时要 @ 的人（私聊时为空）。@所有人 用 Wxid::all()
+ *                  （必须是群主或者管理员才有权限）
  * @return int
  * @Description 发送文本消息
  * @author Changhua
- * @example sendText(" Hello @ 某人1 @ 某人2 ", " xxxxxxxx @ chatroom ",
- * "wxid_xxxxxxxxxxxxx1,wxid_xxxxxxxxxxxxx2");
+ * @example send_text(wechat, " Hello @ 某人1 @ 某人2 ", roomid,
+ * &[wxid1, wxid2]);
  */
 pub fn send_text(
     wechat: &mut WeChat,
     msg: String,
-    receiver: String,
-    aters: String,
-) -> Result<bool, Box<dyn std::error::Error>> {
+    receiver: impl Into<Receiver>,
+    aters: &[Wxid],
+) -> Result<SendStatus, WcfError> {
+    let msg = filter_text(wechat.word_filter.as_deref(), msg)?;
+    send_filtered_text(wechat, msg, receiver, aters)
+}
+
+/** 按 word_filter 过滤文本，未配置时原样返回 */
+pub(crate) fn filter_text(filter: Option<&WordFilter>, msg: String) -> Result<String, WcfError> {
+    match filter {
+        Some(filter) => Ok(filter.apply(&msg)?.into_owned()),
+        None => Ok(msg),
+    }
+}
+
+/** 发送已经过 filter_text 处理的文本，需要知道实际发出内容的调用方使用 */
+pub(crate) fn send_filtered_text(
+    wechat: &mut WeChat,
+    msg: String,
+    receiver: impl Into<Receiver>,
+    aters: &[Wxid],
+) -> Result<SendStatus, WcfError> {
+    let receiver = receiver.into();
+    if !aters.is_empty() && !receiver.is_room() {
+        error!("私聊不能 @: {}", receiver);
+        return Err(WcfError::InvalidArgument(String::from("私聊不能 @")));
+    }
+    throttle(wechat, &receiver, MsgKind::Text)?;
     let text_msg = wcf::TextMsg {
         msg,
-        receiver,
-        aters,
+        receiver: receiver.to_string(),
+        aters: join_aters(aters),
     };
     let req = wcf::Request {
         func: wcf::Functions::FuncSendTxt.into(),
         msg: Some(wcf::request::Msg::Txt(text_msg)),
     };
-    let response = match send_cmd(wechat, req) {
-        Ok(res) => res,
-        Err(e) => {
-            error!("命令发送失败: {}", e);
-            return Err("微信消息发送失败".into());
-        }
-    };
-    if response.is_none() {
-        return Ok(false);
-    }
-    return Ok(true);
-    // match response.unwrap() {
-    //     wcf::response::Msg::Status(status) => {
-    //         return Ok(1 == status);
-    //     }
-    //     _ => {
-    //         return Ok(false);
-    //     }
-    // };
+    exec_send(wechat, req, "微信消息发送")
 }
 
 pub fn send_image(
     wechat: &mut WeChat,
     path: PathBuf,
-    receiver: String,
-) -> Result<bool, Box<dyn std::error::Error>> {
+    receiver: impl Into<Receiver>,
+) -> Result<SendStatus, WcfError> {
+    let receiver = receiver.into();
+    if let Some(status) = precheck(wechat, &path) {
+        return Ok(status);
+    }
+    throttle(wechat, &receiver, MsgKind::Image)?;
     let image_msg = wcf::PathMsg {
-        path: String::from(path.to_str().unwrap()),
-        receiver,
+        path: remote_path(wechat, &path)?,
+        receiver: receiver.to_string(),
     };
     let req = wcf::Request {
         func: wcf::Functions::FuncSendImg.into(),
         msg: Some(wcf::request::Msg::File(image_msg)),
     };
-    let response = match send_cmd(wechat, req) {
-        Ok(res) => res,
-        Err(e) => {
-            error!("命令发送失败: {}", e);
-            return Err("图片发送失败".into());
-        }
-    };
-    println!("{:?}", response);
-    if response.is_none() {
-        return Ok(false);
-    }
-    return Ok(true);
-    // match response.unwrap() {
-    //     wcf::response::Msg::Status(status) => {
-    //         return Ok(1 == status);
-    //     }
-    //     _ => {
-    //         return Ok(false);
-    //     }
-    // };
+    exec_send(wechat, req, "图片发送")
 }
 
 pub fn send_file(
     wechat: &mut WeChat,
     path: PathBuf,
-    receiver: String,
-) -> Result<bool, Box<dyn std::error::Error>> {
+    receiver: impl Into<Receiver>,
+) -> Result<SendStatus, WcfError> {
+    let receiver = receiver.into();
+    if let Some(status) = precheck(wechat, &path) {
+        return Ok(status);
+    }
+    throttle(wechat, &receiver, MsgKind::File)?;
     let image_msg = wcf::PathMsg {
-        path: String::from(path.to_str().unwrap()),
-        receiver,
+        path: remote_path(wechat, &path)?,
+        receiver: receiver.to_string(),
     };
     let req = wcf::Request {
         func: wcf::Functions::FuncSendFile.into(),
         msg: Some(wcf::request::Msg::File(image_msg)),
     };
-    let response = match send_cmd(wechat, req) {
-        Ok(res) => res,
-        Err(e) => {
-            error!("命令发送失败: {}", e);
-            return Err("文件发送失败".into());
-        }
-    };
-    if response.is_none() {
-        return Ok(false);
-    }
-    match response.unwrap() {
-        wcf::response::Msg::Status(status) => {
-            return Ok(1 == status);
-        }
-        _ => {
-            return Ok(false);
-        }
-    };
+    exec_send(wechat, req, "文件发送")
 }
 
+/**
+ * @param xml:      xml 内容，常用卡片可用 xmlmsg 中的 LinkCard 等生成
+ * @param path:     缩略图路径，可为空
+ * @param receiver: 消息接收人
+ * @param xml_type: xml 类型，如：0x21 为小程序
+ * @Description 发送 XML 消息
+ */
 pub fn send_xml(
     wechat: &mut WeChat,
     xml: String,
-    path: PathBuf,
-    receiver: String,
+    path: Option<PathBuf>,
+    receiver: impl Into<Receiver>,
     xml_type: i32,
-) -> Result<bool, Box<dyn std::error::Error>> {
+) -> Result<SendStatus, WcfError> {
+    let receiver = receiver.into();
+    let xml = match &wechat.word_filter {
+        Some(filter) => filter.apply_xml(&xml)?.into_owned(),
+        None => xml,
+    };
+    if let Some(status) = path.as_deref().and_then(|path| precheck(wechat, path)) {
+        return Ok(status);
+    }
+    throttle(wechat, &receiver, MsgKind::Xml)?;
+    let path = match path {
+        Some(path) => remote_path(wechat, &path)?,
+        None => String::new(),
+    };
     let xml_msg = wcf::XmlMsg {
         content: xml,
-        path: String::from(path.to_str().unwrap()),
-        receiver,
+        path,
+        receiver: receiver.to_string(),
         r#type: xml_type,
     };
     let req = wcf::Request {
         func: wcf::Functions::FuncSendXml.into(),
         msg: Some(wcf::request::Msg::Xml(xml_msg)),
     };
-    let response = match send_cmd(wechat, req) {
-        Ok(res) => res,
-        Err(e) => {
-            error!("命令发送失败: {}", e);
-            return Err("微信XML消息发送失败".into());
-        }
-    };
-    if response.is_none() {
-        return Ok(false);
-    }
-    match response.unwrap() {
-        wcf::response::Msg::Status(status) => {
-            return Ok(1 == status);
-        }
-        _ => {
-            return Ok(false);
-        }
-    };
+    exec_send(wechat, req, "微信XML消息发送")
 }
 
 pub fn send_emotion(
     wechat: &mut WeChat,
     path: PathBuf,
-    receiver: String,
-) -> Result<bool, Box<dyn std::error::Error>> {
+    receiver: impl Into<Receiver>,
+) -> Result<SendStatus, WcfError> {
+    let receiver = receiver.into();
+    if let Some(status) = precheck(wechat, &path) {
+        return Ok(status);
+    }
+    throttle(wechat, &receiver, MsgKind::Emotion)?;
     let image_msg = wcf::PathMsg {
-        path: String::from(path.to_str().unwrap()),
-        receiver,
+        path: remote_path(wechat, &path)?,
+        receiver: receiver.to_string(),
     };
     let req = wcf::Request {
         func: wcf::Functions::FuncSendEmotion.into(),
         msg: Some(wcf::request::Msg::File(image_msg)),
     };
-    let response = match send_cmd(wechat, req) {
-        Ok(res) => res,
-        Err(e) => {
-            error!("命令发送失败: {}", e);
-            return Err("微信表情发送失败".into());
-        }
-    };
-    if response.is_none() {
-        return Ok(false);
-    }
-    match response.unwrap() {
-        wcf::response::Msg::Status(status) => {
-            return Ok(1 == status);
-        }
-        _ => {
-            return Ok(false);
-        }
-    };
+    exec_send(wechat, req, "微信表情发送")
 }
 
 pub fn enable_listen(wechat: &mut WeChat) -> Result<nng::Socket, Box<dyn std::error::Error>> {
@@ -583,7 +761,11 @@ pub fn disable_listen(wechat: &mut WeChat) -> Result<bool, Box<dyn std::error::E
     return Ok(true);
 }
 
-pub fn recv_msg(client: &nng::Socket) -> Result<Option<wcf::WxMsg>, Box<dyn std::error::Error>> {
+/** 接收消息，配置了 path_mapper 时 thumb、extra 会映射为客户端路径 */
+pub fn recv_msg(
+    wechat: &WeChat,
+    client: &nng::Socket,
+) -> Result<Option<wcf::WxMsg>, Box<dyn std::error::Error>> {
     let mut msg = match client.recv() {
         Ok(msg) => msg,
         Err(e) => {
@@ -602,7 +784,11 @@ pub fn recv_msg(client: &nng::Socket) -> Result<Option<wcf::WxMsg>, Box<dyn std:
     msg.clear();
     let res_msg = res.msg;
     match res_msg {
-        Some(wcf::response::Msg::Wxmsg(msg)) => {
+        Some(wcf::response::Msg::Wxmsg(mut msg)) => {
+            if let Some(mapper) = &wechat.path_mapper {
+                mapper.map_incoming(&mut msg.thumb);
+                mapper.map_incoming(&mut msg.extra);
+            }
             return Ok(Some(msg));
         }
         _ => {
@@ -648,116 +834,54 @@ pub fn accept_new_friend(
     v4: String,
     scene: i32,
     wechat: &mut WeChat,
-) -> Result<bool, Box<dyn std::error::Error>> {
+) -> Result<SendStatus, WcfError> {
     let req = wcf::Request {
         func: wcf::Functions::FuncAcceptFriend.into(),
         msg: Some(wcf::request::Msg::V(wcf::Verification { v3, v4, scene })),
     };
-    let response = match send_cmd(wechat, req) {
-        Ok(res) => res,
-        Err(e) => {
-            error!("命令发送失败: {}", e);
-            return Err("同意加好友请求失败".into());
-        }
-    };
-    if response.is_none() {
-        return Ok(false);
-    }
-    match response.unwrap() {
-        wcf::response::Msg::Status(status) => {
-            return Ok(status == 1);
-        }
-        _ => {
-            return Ok(false);
-        }
-    };
+    exec_status(wechat, req, STATUS_OK, "同意加好友请求")
 }
 
 pub fn add_chatroom_members(
     roomid: String,
     wxids: String,
     wechat: &mut WeChat,
-) -> Result<bool, Box<dyn std::error::Error>> {
+) -> Result<SendStatus, WcfError> {
     let req = wcf::Request {
         func: wcf::Functions::FuncAddRoomMembers.into(),
         msg: Some(wcf::request::Msg::M(wcf::AddMembers { roomid, wxids })),
     };
-    let response = match send_cmd(wechat, req) {
-        Ok(res) => res,
-        Err(e) => {
-            error!("命令发送失败: {}", e);
-            return Err("微信群加人失败".into());
-        }
-    };
-    if response.is_none() {
-        return Ok(false);
-    }
-    match response.unwrap() {
-        wcf::response::Msg::Status(status) => {
-            return Ok(status == 1);
-        }
-        _ => {
-            return Ok(false);
-        }
-    };
+    exec_status(wechat, req, STATUS_OK, "微信群加人")
 }
 
 pub fn del_chatroom_members(
     roomid: String,
     wxids: String,
     wechat: &mut WeChat,
-) -> Result<bool, Box<dyn std::error::Error>> {
+) -> Result<SendStatus, WcfError> {
     let req = wcf::Request {
         func: wcf::Functions::FuncDelRoomMembers.into(),
         msg: Some(wcf::request::Msg::M(wcf::AddMembers { roomid, wxids })),
     };
-    let response = match send_cmd(wechat, req) {
-        Ok(res) => res,
-        Err(e) => {
-            error!("命令发送失败: {}", e);
-            return Err("微信群踢人失败".into());
-        }
-    };
-    if response.is_none() {
-        return Ok(false);
-    }
-    match response.unwrap() {
-        wcf::response::Msg::Status(status) => {
-            return Ok(status == 1);
-        }
-        _ => {
-            return Ok(false);
-        }
-    };
+    exec_status(wechat, req, STATUS_OK, "微信群踢人")
 }
 
 pub fn decrypt_image(
     src: String,
     dst: String,
     wechat: &mut WeChat,
-) -> Result<bool, Box<dyn std::error::Error>> {
+) -> Result<SendStatus, WcfError> {
+    // src 通常来自消息的 extra，没有规则覆盖时 map_incoming 保留的就是服务端路径
+    let src = match &wechat.path_mapper {
+        Some(mapper) => mapper.to_remote(Path::new(&src)),
+        None => src,
+    };
+    let dst = remote_path(wechat, Path::new(&dst))?;
     let req = wcf::Request {
         func: wcf::Functions::FuncDecryptImage.into(),
         msg: Some(wcf::request::Msg::Dec(wcf::DecPath { src, dst })),
     };
-    let response = match send_cmd(wechat, req) {
-        Ok(res) => res,
-        Err(e) => {
-            error!("命令发送失败: {}", e);
-            return Err("图片解密失败".into());
-        }
-    };
-    if response.is_none() {
-        return Ok(false);
-    }
-    match response.unwrap() {
-        wcf::response::Msg::Status(status) => {
-            return Ok(status == 1);
-        }
-        _ => {
-            return Ok(false);
-        }
-    };
+    exec_status(wechat, req, STATUS_OK, "图片解密")
 }
 
 pub fn recv_transfer(
@@ -765,7 +889,7 @@ pub fn recv_transfer(
     transferid: String,
     transcationid: String,
     wechat: &mut WeChat,
-) -> Result<bool, Box<dyn std::error::Error>> {
+) -> Result<SendStatus, WcfError> {
     let req = wcf::Request {
         func: wcf::Functions::FuncRecvTransfer.into(),
         msg: Some(wcf::request::Msg::Tf(wcf::Transfer {
@@ -774,50 +898,16 @@ pub fn recv_transfer(
             taid: transcationid,
         })),
     };
-    let response = match send_cmd(wechat, req) {
-        Ok(res) => res,
-        Err(e) => {
-            error!("命令发送失败: {}", e);
-            return Err("接收转账失败".into());
-        }
-    };
-    if response.is_none() {
-        return Ok(false);
-    }
-    match response.unwrap() {
-        wcf::response::Msg::Status(status) => {
-            return Ok(status == 1);
-        }
-        _ => {
-            return Ok(false);
-        }
-    };
+    exec_status(wechat, req, STATUS_OK, "接收转账")
 }
 
 /** 刷新朋友圈 */
-pub fn refresh_pyq(id: u64, wechat: &mut WeChat) -> Result<bool, Box<dyn std::error::Error>> {
+pub fn refresh_pyq(id: u64, wechat: &mut WeChat) -> Result<SendStatus, WcfError> {
     let req = wcf::Request {
         func: wcf::Functions::FuncRefreshPyq.into(),
         msg: Some(wcf::request::Msg::Ui64(id)),
     };
-    let response = match send_cmd(wechat, req) {
-        Ok(res) => res,
-        Err(e) => {
-            error!("命令发送失败: {}", e);
-            return Err("接收转账失败".into());
-        }
-    };
-    if response.is_none() {
-        return Ok(false);
-    }
-    match response.unwrap() {
-        wcf::response::Msg::Status(status) => {
-            return Ok(status != -1);
-        }
-        _ => {
-            return Ok(false);
-        }
-    };
+    exec_status(wechat, req, STATUS_OK, "刷新朋友圈")
 }
 
 mod test {
@@ -859,8 +949,8 @@ mod test {
         let status = crate::wechat::send_text(
             &mut wechat,
             String::from("Hello, wcferry!"),
-            String::from("filehelper"),
-            String::from(""),
+            crate::receiver::SpecialAccount::FileHelper,
+            &[],
         )
         .unwrap();
         println!("Success: {}", status);
@@ -874,7 +964,7 @@ mod test {
         let status = crate::wechat::send_image(
             &mut wechat,
             PathBuf::from("C:\\Users\\Administrator\\Pictures\\1.jpg"),
-            String::from("filehelper"),
+            crate::receiver::SpecialAccount::FileHelper,
         )
         .unwrap();
         println!("Success: {}", status);
@@ -883,10 +973,10 @@ mod test {
     #[test]
     fn test_recv_msg() {
         let mut wechat = crate::wechat::WeChat::default();
-        let mut socket = crate::wechat::enable_listen(&mut wechat).unwrap();
+        let socket = crate::wechat::enable_listen(&mut wechat).unwrap();
         for _index in 0..5 {
             let _ = crate::wechat::refresh_pyq(0, &mut wechat);
-            let msg = crate::wechat::recv_msg(&mut socket).unwrap();
+            let msg = crate::wechat::recv_msg(&wechat, &socket).unwrap();
             println!("WxMsg: {:?}", msg);
             println!("--------------------------------------------------");
         }
@@ -933,6 +1023,14 @@ mod test {
         println!("Status: {}", status);
     }
 
+    #[test]
+    fn test_get_chatroom_members() {
+        let mut wechat = crate::wechat::WeChat::default();
+        let roomid = "34476879773@chatroom".parse().unwrap();
+        let members = crate::wechat::get_chatroom_members(&mut wechat, &roomid).unwrap();
+        println!("Members: {:?}", members);
+    }
+
     #[test]
     fn test_get_user_info() {
         let mut wechat = crate::wechat::WeChat::default();
@@ -0,0 +1,332 @@
+use std::path::PathBuf;
+
+use crate::{
+    error::WcfError,
+    message::appmsg::{
+        APP_TYPE_FILE, APP_TYPE_LINK, APP_TYPE_MINI_PROGRAM, APP_TYPE_MUSIC, APP_TYPE_QUOTE,
+    },
+    receiver::Receiver,
+    status::SendStatus,
+    wechat::{self, WeChat},
+};
+
+/** XmlMsg.type：普通 appmsg 卡片 */
+pub const XML_TYPE_APP: i32 = 0x31;
+/** XmlMsg.type：小程序 */
+pub const XML_TYPE_MINI_PROGRAM: i32 = 0x21;
+
+/** 可直接交给 send_xml 的 XML 消息 */
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct XmlMessage {
+    pub content: String,
+    /** 缩略图路径 */
+    pub path: Option<PathBuf>,
+    pub xml_type: i32,
+}
+
+impl XmlMessage {
+    pub fn send(
+        &self,
+        wechat: &mut WeChat,
+        receiver: impl Into<Receiver>,
+    ) -> Result<SendStatus, WcfError> {
+        wechat::send_xml(
+            wechat,
+            self.content.clone(),
+            self.path.clone(),
+            receiver,
+            self.xml_type,
+        )
+    }
+}
+
+/** XML 文本转义 */
+pub fn escape(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/** 生成 `<tag>escaped</tag>` */
+fn tag(name: &str, value: &str) -> String {
+    format!("<{}>{}</{}>", name, escape(value), name)
+}
+
+fn appmsg(app_type: u32, body: &str) -> String {
+    format!(
+        "<?xml version=\"1.0\"?><msg><appmsg appid=\"\" sdkver=\"0\">{}{}</appmsg></msg>",
+        tag("type", &app_type.to_string()),
+        body
+    )
+}
+
+/** 链接 / 文章分享 */
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct LinkCard {
+    pub title: String,
+    pub desc: String,
+    pub url: String,
+    /** 网络缩略图地址 */
+    pub thumb_url: String,
+    /** 来源显示名，如公众号名称 */
+    pub source: String,
+    /** 本地缩略图路径 */
+    pub thumb: Option<PathBuf>,
+}
+
+impl LinkCard {
+    pub fn build(&self) -> XmlMessage {
+        let body = [
+            tag("title", &self.title),
+            tag("des", &self.desc),
+            tag("url", &self.url),
+            tag("thumburl", &self.thumb_url),
+            tag("sourcedisplayname", &self.source),
+        ]
+        .concat();
+        XmlMessage {
+            content: appmsg(APP_TYPE_LINK, &body),
+            path: self.thumb.clone(),
+            xml_type: XML_TYPE_APP,
+        }
+    }
+}
+
+/** 音乐分享 */
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct MusicCard {
+    pub title: String,
+    pub desc: String,
+    /** 点击卡片打开的页面 */
+    pub url: String,
+    /** 音频地址 */
+    pub data_url: String,
+    pub thumb_url: String,
+    pub thumb: Option<PathBuf>,
+}
+
+impl MusicCard {
+    pub fn build(&self) -> XmlMessage {
+        let body = [
+            tag("title", &self.title),
+            tag("des", &self.desc),
+            tag("url", &self.url),
+            tag("lowurl", &self.url),
+            tag("dataurl", &self.data_url),
+            tag("lowdataurl", &self.data_url),
+            tag("thumburl", &self.thumb_url),
+        ]
+        .concat();
+        XmlMessage {
+            content: appmsg(APP_TYPE_MUSIC, &body),
+            path: self.thumb.clone(),
+            xml_type: XML_TYPE_APP,
+        }
+    }
+}
+
+/** 小程序 */
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct MiniProgramCard {
+    pub title: String,
+    /** 小程序原始 id，如 gh_xxxxxxxx@app */
+    pub username: String,
+    pub appid: String,
+    pub pagepath: String,
+    /** 小程序名称 */
+    pub source: String,
+    pub icon_url: String,
+    pub thumb: Option<PathBuf>,
+}
+
+impl MiniProgramCard {
+    pub fn build(&self) -> XmlMessage {
+        let weappinfo = [
+            tag("username", &self.username),
+            tag("appid", &self.appid),
+            tag("pagepath", &self.pagepath),
+            tag("type", "2"),
+            tag("weappiconurl", &self.icon_url),
+        ]
+        .concat();
+        let body = [
+            tag("title", &self.title),
+            tag("sourceusername", &self.username),
+            tag("sourcedisplayname", &self.source),
+            format!("<weappinfo>{}</weappinfo>", weappinfo),
+        ]
+        .concat();
+        XmlMessage {
+            content: appmsg(APP_TYPE_MINI_PROGRAM, &body),
+            path: self.thumb.clone(),
+            xml_type: XML_TYPE_MINI_PROGRAM,
+        }
+    }
+}
+
+/** 文件卡片 */
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct FileCard {
+    pub name: String,
+    pub size: u64,
+    /** 扩展名（不带点） */
+    pub ext: String,
+    pub md5: String,
+    /** CDN 附件 id */
+    pub attach_id: String,
+}
+
+impl FileCard {
+    pub fn build(&self) -> XmlMessage {
+        let appattach = [
+            tag("totallen", &self.size.to_string()),
+            tag("attachid", &self.attach_id),
+            tag("fileext", &self.ext),
+        ]
+        .concat();
+        let body = [
+            tag("title", &self.name),
+            format!("<appattach>{}</appattach>", appattach),
+            tag("md5", &self.md5),
+        ]
+        .concat();
+        XmlMessage {
+            content: appmsg(APP_TYPE_FILE, &body),
+            path: None,
+            xml_type: XML_TYPE_APP,
+        }
+    }
+}
+
+/** 引用回复（appmsg type 57） */
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct QuoteReply {
+    /** 回复内容 */
+    pub text: String,
+    /** 被引用消息的 id */
+    pub msg_id: u64,
+    /** 被引用消息的类型 */
+    pub msg_type: u32,
+    /** 被引用消息的发送者 wxid */
+    pub sender: String,
+    /** 被引用消息所在的群，私聊为空 */
+    pub roomid: String,
+    /** 被引用消息发送者的显示名 */
+    pub display_name: String,
+    /** 被引用消息的内容 */
+    pub content: String,
+}
+
+impl QuoteReply {
+    pub fn build(&self) -> XmlMessage {
+        let (fromusr, chatusr) = if self.roomid.is_empty() {
+            (self.sender.as_str(), "")
+        } else {
+            (self.roomid.as_str(), self.sender.as_str())
+        };
+        let refermsg = [
+            tag("type", &self.msg_type.to_string()),
+            tag("svrid", &self.msg_id.to_string()),
+            tag("fromusr", fromusr),
+            tag("chatusr", chatusr),
+            tag("displayname", &self.display_name),
+            tag("content", &self.content),
+        ]
+        .concat();
+        let body = [
+            tag("title", &self.text),
+            format!("<refermsg>{}</refermsg>", refermsg),
+        ]
+        .concat();
+        XmlMessage {
+            content: appmsg(APP_TYPE_QUOTE, &body),
+            path: None,
+            xml_type: XML_TYPE_APP,
+        }
+    }
+}
+
+mod test {
+
+    #[test]
+    fn test_escape() {
+        assert_eq!(
+            crate::xmlmsg::escape(r#"<a href="x?a=1&b=2">'</a>"#),
+            "&lt;a href=&quot;x?a=1&amp;b=2&quot;&gt;&apos;&lt;/a&gt;"
+        );
+    }
+
+    #[test]
+    fn test_build_round_trip() {
+        use crate::message::appmsg::AppMessage;
+        use crate::xmlmsg::{LinkCard, MiniProgramCard, QuoteReply, XML_TYPE_MINI_PROGRAM};
+
+        let link = LinkCard {
+            title: String::from("标题 <1>"),
+            desc: String::from("a & b"),
+            url: String::from("https://example.com/?a=1&b=2"),
+            ..Default::default()
+        }
+        .build();
+        assert!(link.path.is_none());
+        match AppMessage::parse(&link.content).unwrap() {
+            AppMessage::Link {
+                title, desc, url, ..
+            } => {
+                assert_eq!(title, "标题 <1>");
+                assert_eq!(desc, "a & b");
+                assert_eq!(url, "https://example.com/?a=1&b=2");
+            }
+            other => panic!("unexpected: {:?}", other),
+        }
+
+        let mini = MiniProgramCard {
+            title: String::from("小程序"),
+            username: String::from("gh_123@app"),
+            appid: String::from("wx1234567890"),
+            pagepath: String::from("pages/index.html?id=1&from=bot"),
+            thumb: Some(std::path::PathBuf::from("C:\\thumb.jpg")),
+            ..Default::default()
+        }
+        .build();
+        assert_eq!(mini.xml_type, XML_TYPE_MINI_PROGRAM);
+        match AppMessage::parse(&mini.content).unwrap() {
+            AppMessage::MiniProgram {
+                appid, pagepath, ..
+            } => {
+                assert_eq!(appid, "wx1234567890");
+                assert_eq!(pagepath, "pages/index.html?id=1&from=bot");
+            }
+            other => panic!("unexpected: {:?}", other),
+        }
+
+        let quote = QuoteReply {
+            text: String::from("收到"),
+            msg_id: 8541264785412365478,
+            msg_type: 1,
+            sender: String::from("wxid_abc"),
+            roomid: String::from("34476879773@chatroom"),
+            display_name: String::from("张三"),
+            content: String::from("<明天>开会"),
+        }
+        .build();
+        match AppMessage::parse(&quote.content).unwrap() {
+            AppMessage::Quote { title, refer } => {
+                assert_eq!(title, "收到");
+                assert_eq!(refer.id, 8541264785412365478);
+                assert_eq!(refer.sender, "wxid_abc");
+                assert_eq!(refer.content, "<明天>开会");
+            }
+            other => panic!("unexpected: {:?}", other),
+        }
+    }
+}